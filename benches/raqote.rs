@@ -0,0 +1,275 @@
+//! Benchmarks of the [`Raqote`] renderer
+//!
+//! Everything is drawn on an offscreen draw target, so the benchmarks run
+//! without a window. To run them you need to use the command
+//! `cargo bench`
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use hyber::renderer::{DrawImageOptions, RenderInstruction, RenderInstructionCollection, Renderer};
+use hyber::util::{Color, Vector2D};
+
+use hyber_raqote::keys::{key_events, Modifiers};
+use hyber_raqote::Raqote;
+
+const WIDTH: i32 = 800;
+const HEIGHT: i32 = 600;
+
+/// Image drawn by the image benchmarks
+const IMAGE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/rust.png");
+
+fn point(x: f64, y: f64) -> Vector2D {
+    Vector2D::new(x, y)
+}
+
+fn color() -> Color {
+    Color::new(255, 200, 60, 30)
+}
+
+fn clip_point() -> Vector2D {
+    point(0., 0.)
+}
+
+fn clip_size() -> Vector2D {
+    point(WIDTH as f64, HEIGHT as f64)
+}
+
+fn primitives(c: &mut Criterion) {
+    let instructions = vec![
+        ("clear", RenderInstruction::Clear { color: color() }),
+        (
+            "point",
+            RenderInstruction::DrawPoint {
+                point: point(400., 300.),
+                color: color(),
+                clip_point: clip_point(),
+                clip_size: clip_size(),
+            },
+        ),
+        (
+            "line",
+            RenderInstruction::DrawLine {
+                point_a: point(20., 30.),
+                point_b: point(700., 550.),
+                color: color(),
+                clip_point: clip_point(),
+                clip_size: clip_size(),
+            },
+        ),
+        (
+            "arc",
+            RenderInstruction::DrawArc {
+                point: point(400., 300.),
+                r: 200,
+                s_ang: 0,
+                e_ang: 270,
+                color: color(),
+                clip_point: clip_point(),
+                clip_size: clip_size(),
+            },
+        ),
+        (
+            "circle",
+            RenderInstruction::DrawCircle {
+                point: point(400., 300.),
+                r: 200,
+                color: color(),
+                clip_point: clip_point(),
+                clip_size: clip_size(),
+            },
+        ),
+        (
+            "rect",
+            RenderInstruction::DrawRect {
+                point: point(100., 100.),
+                size: point(600., 400.),
+                color: color(),
+                clip_point: clip_point(),
+                clip_size: clip_size(),
+            },
+        ),
+        (
+            "triangle",
+            RenderInstruction::DrawTriangle {
+                point_a: point(400., 50.),
+                point_b: point(50., 550.),
+                point_c: point(750., 550.),
+                color: color(),
+                clip_point: clip_point(),
+                clip_size: clip_size(),
+            },
+        ),
+    ];
+
+    let mut group = c.benchmark_group("primitives");
+    let mut raqote = Raqote::new(WIDTH, HEIGHT);
+    for (name, instruction) in instructions.iter() {
+        group.bench_function(*name, |b| b.iter(|| raqote.draw(black_box(instruction))));
+    }
+    group.finish();
+}
+
+fn text(c: &mut Criterion) {
+    let mut group = c.benchmark_group("text");
+    let mut raqote = Raqote::new(WIDTH, HEIGHT);
+    for font_size in [12, 24, 48, 96].iter() {
+        let instruction = RenderInstruction::DrawText {
+            point: point(10., *font_size as f64 + 10.),
+            font_size: *font_size,
+            string: String::from("The quick brown fox jumps over the lazy dog"),
+            color: color(),
+            clip_point: clip_point(),
+            clip_size: clip_size(),
+        };
+        group.bench_with_input(
+            BenchmarkId::from_parameter(font_size),
+            &instruction,
+            |b, instruction| b.iter(|| raqote.draw(instruction)),
+        );
+    }
+    group.finish();
+}
+
+fn images(c: &mut Criterion) {
+    let mut group = c.benchmark_group("image");
+    let mut raqote = Raqote::new(WIDTH, HEIGHT);
+    let options = vec![
+        ("original", DrawImageOptions::OriginalSize),
+        ("half", DrawImageOptions::ResizeMultiplyer { mult: 0.5 }),
+        ("double", DrawImageOptions::ResizeMultiplyer { mult: 2. }),
+        (
+            "resize",
+            DrawImageOptions::Resize {
+                width: WIDTH as usize,
+                height: HEIGHT as usize,
+            },
+        ),
+    ];
+    for (name, options) in options {
+        let instruction = RenderInstruction::DrawImage {
+            point: point(0., 0.),
+            path: String::from(IMAGE),
+            options,
+            clip_point: clip_point(),
+            clip_size: clip_size(),
+        };
+        group.bench_function(name, |b| b.iter(|| raqote.draw(&instruction)));
+    }
+    group.finish();
+}
+
+/// Collection of `widgets` buttons laid out in a grid, each made of a
+/// background, a border and a label, where `label` changes the text of
+/// the first one
+fn widget_tree(widgets: usize, label: &str) -> RenderInstructionCollection {
+    let mut collection = RenderInstructionCollection::new();
+    collection.pairs.insert(0, vec![RenderInstruction::Clear { color: color() }]);
+
+    let columns = 10;
+    let width = WIDTH as f64 / columns as f64;
+    let height = 40.;
+    for index in 0..widgets {
+        let x = (index % columns) as f64 * width;
+        let y = (index / columns) as f64 * height;
+        let string = if index == 0 {
+            String::from(label)
+        } else {
+            format!("Button {}", index)
+        };
+        collection.pairs.insert(
+            index + 1,
+            vec![
+                RenderInstruction::DrawRect {
+                    point: point(x, y),
+                    size: point(width, height),
+                    color: Color::new(255, 40, 40, 40),
+                    clip_point: point(x, y),
+                    clip_size: point(width, height),
+                },
+                RenderInstruction::DrawLine {
+                    point_a: point(x, y + height),
+                    point_b: point(x + width, y + height),
+                    color: color(),
+                    clip_point: point(x, y),
+                    clip_size: point(width, height),
+                },
+                RenderInstruction::DrawText {
+                    point: point(x + 4., y + 24.),
+                    font_size: 14,
+                    string,
+                    color: Color::new(255, 255, 255, 255),
+                    clip_point: point(x, y),
+                    clip_size: point(width, height),
+                },
+            ],
+        );
+    }
+    collection
+}
+
+fn collections(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_collection");
+    for widgets in [10, 100, 500].iter() {
+        let first = widget_tree(*widgets, "First");
+        let second = widget_tree(*widgets, "Second");
+        let mut raqote = Raqote::new(WIDTH, HEIGHT);
+
+        // Every frame is drawn from scratch
+        group.bench_with_input(BenchmarkId::new("full", widgets), &first, |b, tree| {
+            b.iter(|| {
+                raqote.invalidate();
+                raqote.render_collection(tree, WIDTH, HEIGHT)
+            })
+        });
+
+        // Only a widget changes between frames
+        let mut flip = false;
+        group.bench_with_input(BenchmarkId::new("partial", widgets), widgets, |b, _| {
+            b.iter(|| {
+                flip = !flip;
+                let tree = if flip { &first } else { &second };
+                raqote.render_collection(tree, WIDTH, HEIGHT)
+            })
+        });
+
+        // Nothing changes between frames
+        group.bench_with_input(BenchmarkId::new("unchanged", widgets), &first, |b, tree| {
+            b.iter(|| raqote.render_collection(tree, WIDTH, HEIGHT))
+        });
+    }
+    group.finish();
+}
+
+fn key_mapping(c: &mut Criterion) {
+    let keys = vec![
+        minifb::Key::A,
+        minifb::Key::C,
+        minifb::Key::V,
+        minifb::Key::Key5,
+        minifb::Key::Enter,
+        minifb::Key::Space,
+        minifb::Key::Left,
+        minifb::Key::F5,
+        minifb::Key::NumPadPlus,
+        minifb::Key::LeftShift,
+    ];
+    let modifiers = Modifiers {
+        control: true,
+        ..Modifiers::default()
+    };
+
+    c.bench_function("key_mapping", |b| {
+        b.iter(|| {
+            let pressed = key_events(black_box(&keys), true, modifiers);
+            let released = key_events(black_box(&keys), false, modifiers);
+            pressed
+                .into_iter()
+                .chain(released)
+                .map(Raqote::map_events)
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+criterion_group!(benches, primitives, text, images, collections, key_mapping);
+criterion_main!(benches);
@@ -0,0 +1,644 @@
+//! Color glyphs of emoji and bitmap fonts
+//!
+//! [`font_kit`](`crate`) only rasterizes monochrome outlines, so the color
+//! formats are read straight from the font tables:
+//! * `CBLC`/`CBDT` and `sbix` embedded PNG bitmaps, scaled to the
+//!   requested font size;
+//! * `COLR` version 0 layers, colored with the `CPAL` palette.
+
+use crate::pixels::rgba_to_argb;
+
+use font_kit::font::Font;
+
+use image::imageops::FilterType;
+use image::ImageFormat;
+
+use std::collections::HashMap;
+
+/// Memory, in bytes, after which the scaled bitmaps are dropped
+const BITMAPS_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Palette index of `COLR` layers drawn with the text color
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+/// Layer of a `COLR` glyph
+#[derive(Clone, Copy)]
+pub struct ColorLayer {
+    /// Identifier of the outline glyph drawn by the layer
+    pub glyph_id: u32,
+    /// Color of the layer, as alpha, red, green and blue, or `None`
+    /// when the layer uses the text color
+    pub color: Option<(u8, u8, u8, u8)>,
+}
+
+/// Bitmap glyph scaled to a font size
+pub struct ColorBitmap {
+    /// Horizontal offset of the bitmap from the glyph's origin
+    pub left: f32,
+    /// Vertical offset of the bitmap's top from the baseline
+    pub top: f32,
+    /// Width of the bitmap in pixels
+    pub width: i32,
+    /// Height of the bitmap in pixels
+    pub height: i32,
+    /// Premultiplied ARGB pixels of the bitmap
+    pub data: Vec<u32>,
+}
+
+/// Color tables of a font
+pub struct ColorFont {
+    /// `CBLC` and `CBDT` tables
+    cbdt: Option<(Box<[u8]>, Box<[u8]>)>,
+    /// `sbix` table and the number of glyphs of the font
+    sbix: Option<(Box<[u8]>, u16)>,
+    /// `COLR` table
+    colr: Option<Box<[u8]>>,
+    /// First palette of the `CPAL` table, as alpha, red, green and blue
+    palette: Vec<(u8, u8, u8, u8)>,
+}
+
+/// Builds the tag of a font table from its name
+fn tag(name: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*name)
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+
+fn read_i8(data: &[u8], offset: usize) -> Option<i8> {
+    read_u8(data, offset).map(|value| value as i8)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// PNG image of a bitmap glyph, before scaling
+struct EmbeddedPng<'a> {
+    /// Size in pixels per em of the strike holding the image
+    ppem: f32,
+    /// Horizontal offset of the image from the glyph's origin, in strike pixels
+    left: f32,
+    /// Vertical offset of the image's top from the baseline, in strike pixels
+    top: Option<f32>,
+    /// Vertical offset of the image's bottom from the baseline, in strike pixels
+    bottom: Option<f32>,
+    data: &'a [u8],
+}
+
+impl ColorFont {
+    /// Reads the color tables of `font`, or `None` if it has none
+    pub fn load(font: &Font) -> Option<ColorFont> {
+        let cbdt = match (
+            font.load_font_table(tag(b"CBLC")),
+            font.load_font_table(tag(b"CBDT")),
+        ) {
+            (Some(cblc), Some(cbdt)) => Some((cblc, cbdt)),
+            _ => None,
+        };
+        let sbix = font.load_font_table(tag(b"sbix")).and_then(|sbix| {
+            let maxp = font.load_font_table(tag(b"maxp"))?;
+            Some((sbix, read_u16(&maxp, 4)?))
+        });
+        let colr = font.load_font_table(tag(b"COLR"));
+        let palette = font
+            .load_font_table(tag(b"CPAL"))
+            .and_then(|cpal| read_palette(&cpal))
+            .unwrap_or_default();
+
+        if cbdt.is_none() && sbix.is_none() && colr.is_none() {
+            return None;
+        }
+        Some(ColorFont {
+            cbdt,
+            sbix,
+            colr,
+            palette,
+        })
+    }
+
+    /// Gets the `COLR` layers of glyph `glyph_id`, from bottom to top
+    pub fn layers(&self, glyph_id: u32) -> Option<Vec<ColorLayer>> {
+        let colr = self.colr.as_ref()?;
+        if read_u16(colr, 0)? > 1 {
+            return None;
+        }
+        let base_count = read_u16(colr, 2)? as usize;
+        let base_offset = read_u32(colr, 4)? as usize;
+        let layers_offset = read_u32(colr, 8)? as usize;
+
+        // Base glyph records are sorted by glyph identifier
+        let (mut low, mut high) = (0, base_count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let record = base_offset + middle * 6;
+            let id = read_u16(colr, record)? as u32;
+            if id < glyph_id {
+                low = middle + 1;
+            } else if id > glyph_id {
+                high = middle;
+            } else {
+                let first = read_u16(colr, record + 2)? as usize;
+                let count = read_u16(colr, record + 4)? as usize;
+                let mut layers = Vec::with_capacity(count);
+                for index in first..first + count {
+                    let layer = layers_offset + index * 4;
+                    let palette_index = read_u16(colr, layer + 2)?;
+                    layers.push(ColorLayer {
+                        glyph_id: read_u16(colr, layer)? as u32,
+                        color: if palette_index == FOREGROUND_PALETTE_INDEX {
+                            None
+                        } else {
+                            self.palette.get(palette_index as usize).copied()
+                        },
+                    });
+                }
+                return Some(layers);
+            }
+        }
+        None
+    }
+
+    /// Gets the bitmap of glyph `glyph_id` scaled to `size` pixels per em
+    pub fn bitmap(&self, glyph_id: u32, size: f32) -> Option<ColorBitmap> {
+        let png = self
+            .cbdt_png(glyph_id, size)
+            .or_else(|| self.sbix_png(glyph_id, size))?;
+        if png.ppem == 0. {
+            return None;
+        }
+        let image = image::load_from_memory_with_format(png.data, ImageFormat::Png)
+            .ok()?
+            .into_rgba8();
+        let scale = size / png.ppem;
+        let width = ((image.width() as f32 * scale).round() as u32).max(1);
+        let height = ((image.height() as f32 * scale).round() as u32).max(1);
+        let image = image::imageops::resize(&image, width, height, FilterType::Triangle);
+
+        let top = match (png.top, png.bottom) {
+            (Some(top), _) => -top * scale,
+            (None, Some(bottom)) => -bottom * scale - height as f32,
+            (None, None) => -(height as f32),
+        };
+        let data = rgba_to_argb(image.as_raw());
+
+        Some(ColorBitmap {
+            left: png.left * scale,
+            top,
+            width: width as i32,
+            height: height as i32,
+            data,
+        })
+    }
+
+    /// Finds the PNG of glyph `glyph_id` in the `CBDT` strike best suited to `size`
+    fn cbdt_png(&self, glyph_id: u32, size: f32) -> Option<EmbeddedPng> {
+        let (cblc, cbdt) = self.cbdt.as_ref()?;
+        let glyph_id = glyph_id as u16;
+
+        let mut strike: Option<(usize, u8)> = None;
+        for index in 0..read_u32(cblc, 4)? as usize {
+            let record = 8 + index * 48;
+            let start = read_u16(cblc, record + 40)?;
+            let end = read_u16(cblc, record + 42)?;
+            let ppem = read_u8(cblc, record + 45)?;
+            if glyph_id < start || glyph_id > end {
+                continue;
+            }
+            if is_better_strike(strike.map(|(_, best)| best as f32), ppem as f32, size) {
+                strike = Some((record, ppem));
+            }
+        }
+        let (record, ppem) = strike?;
+
+        let array_offset = read_u32(cblc, record)? as usize;
+        for index in 0..read_u32(cblc, record + 8)? as usize {
+            let entry = array_offset + index * 8;
+            let first = read_u16(cblc, entry)?;
+            let last = read_u16(cblc, entry + 2)?;
+            if glyph_id < first || glyph_id > last {
+                continue;
+            }
+            let subtable = array_offset + read_u32(cblc, entry + 4)? as usize;
+            let index_format = read_u16(cblc, subtable)?;
+            let image_format = read_u16(cblc, subtable + 2)?;
+            let image_offset = read_u32(cblc, subtable + 4)? as usize;
+            let position = (glyph_id - first) as usize;
+
+            // Locates the glyph data and, for formats 2 and 5, the shared metrics
+            let (start, metrics) = match index_format {
+                1 => (read_u32(cblc, subtable + 8 + position * 4)? as usize, None),
+                2 => {
+                    let image_size = read_u32(cblc, subtable + 8)? as usize;
+                    (position * image_size, Some(subtable + 12))
+                }
+                3 => (read_u16(cblc, subtable + 8 + position * 2)? as usize, None),
+                4 => {
+                    let count = read_u32(cblc, subtable + 8)? as usize;
+                    let pair = (0..count)
+                        .map(|pair| subtable + 12 + pair * 4)
+                        .take_while(|pair| pair + 4 <= cblc.len())
+                        .find(|pair| read_u16(cblc, *pair) == Some(glyph_id))?;
+                    (read_u16(cblc, pair + 2)? as usize, None)
+                }
+                5 => {
+                    let image_size = read_u32(cblc, subtable + 8)? as usize;
+                    let count = read_u32(cblc, subtable + 20)? as usize;
+                    let position = (0..count)
+                        .take_while(|id| subtable + 26 + id * 2 <= cblc.len())
+                        .find(|id| read_u16(cblc, subtable + 24 + id * 2) == Some(glyph_id))?;
+                    (position * image_size, Some(subtable + 12))
+                }
+                _ => return None,
+            };
+            let glyph = image_offset + start;
+
+            // Reads the metrics and the PNG of the glyph
+            let (left, top, length, data) = match image_format {
+                17 => (
+                    read_i8(cbdt, glyph + 2)?,
+                    read_i8(cbdt, glyph + 3)?,
+                    read_u32(cbdt, glyph + 5)? as usize,
+                    glyph + 9,
+                ),
+                18 => (
+                    read_i8(cbdt, glyph + 2)?,
+                    read_i8(cbdt, glyph + 3)?,
+                    read_u32(cbdt, glyph + 8)? as usize,
+                    glyph + 12,
+                ),
+                19 => {
+                    let metrics = metrics?;
+                    (
+                        read_i8(cblc, metrics + 2)?,
+                        read_i8(cblc, metrics + 3)?,
+                        read_u32(cbdt, glyph)? as usize,
+                        glyph + 4,
+                    )
+                }
+                _ => return None,
+            };
+            return Some(EmbeddedPng {
+                ppem: ppem as f32,
+                left: left as f32,
+                top: Some(top as f32),
+                bottom: None,
+                data: cbdt.get(data..data + length)?,
+            });
+        }
+        None
+    }
+
+    /// Finds the PNG of glyph `glyph_id` in the `sbix` strike best suited to `size`
+    fn sbix_png(&self, glyph_id: u32, size: f32) -> Option<EmbeddedPng> {
+        let (sbix, glyph_count) = self.sbix.as_ref()?;
+        if glyph_id >= *glyph_count as u32 {
+            return None;
+        }
+
+        let mut strike: Option<(usize, u16)> = None;
+        for index in 0..read_u32(sbix, 4)? as usize {
+            let offset = read_u32(sbix, 8 + index * 4)? as usize;
+            let ppem = read_u16(sbix, offset)?;
+            if is_better_strike(strike.map(|(_, best)| best as f32), ppem as f32, size) {
+                strike = Some((offset, ppem));
+            }
+        }
+        let (offset, ppem) = strike?;
+
+        let start = offset + read_u32(sbix, offset + 4 + glyph_id as usize * 4)? as usize;
+        let end = offset + read_u32(sbix, offset + 8 + glyph_id as usize * 4)? as usize;
+        if end <= start + 8 || sbix.get(start + 4..start + 8)? != b"png " {
+            return None;
+        }
+        Some(EmbeddedPng {
+            ppem: ppem as f32,
+            left: read_i16(sbix, start)? as f32,
+            top: None,
+            bottom: Some(read_i16(sbix, start + 2)? as f32),
+            data: sbix.get(start + 8..end)?,
+        })
+    }
+}
+
+/// Whether a strike of `candidate` pixels per em suits `size` better than
+/// the `best` one found so far, preferring the smallest strike not smaller
+/// than the size, and otherwise the largest one
+fn is_better_strike(best: Option<f32>, candidate: f32, size: f32) -> bool {
+    match best {
+        None => true,
+        Some(best) if best >= size => candidate >= size && candidate < best,
+        Some(best) => candidate > best,
+    }
+}
+
+/// Reads the first palette of a `CPAL` table
+fn read_palette(cpal: &[u8]) -> Option<Vec<(u8, u8, u8, u8)>> {
+    let entries = read_u16(cpal, 2)? as usize;
+    let records = read_u32(cpal, 8)? as usize;
+    let first = read_u16(cpal, 12)? as usize;
+    (first..first + entries)
+        .map(|index| {
+            // Color records are stored as blue, green, red and alpha
+            let record = records + index * 4;
+            Some((
+                read_u8(cpal, record + 3)?,
+                read_u8(cpal, record + 2)?,
+                read_u8(cpal, record + 1)?,
+                read_u8(cpal, record)?,
+            ))
+        })
+        .collect()
+}
+
+/// Color tables and scaled bitmaps of the fonts used by the text primitives
+#[derive(Default)]
+pub struct ColorGlyphCache {
+    /// Color tables of each font, by the identifier given by the
+    /// [`FontCache`](crate::text::FontCache)
+    fonts: HashMap<usize, Option<ColorFont>>,
+    /// Bitmaps by font identifier, glyph identifier and bits of the font size
+    bitmaps: HashMap<(usize, u32, u32), Option<ColorBitmap>>,
+    /// Memory, in bytes, used by the bitmaps
+    size: usize,
+}
+
+impl ColorGlyphCache {
+    pub fn new() -> Self {
+        ColorGlyphCache::default()
+    }
+
+    fn font(&mut self, font_id: usize, font: &Font) -> Option<&ColorFont> {
+        self.fonts
+            .entry(font_id)
+            .or_insert_with(|| ColorFont::load(font))
+            .as_ref()
+    }
+
+    /// Gets the `COLR` layers of glyph `glyph_id`, if it has any
+    pub fn layers(&mut self, font_id: usize, font: &Font, glyph_id: u32) -> Option<Vec<ColorLayer>> {
+        self.font(font_id, font)?.layers(glyph_id)
+    }
+
+    /// Gets the bitmap of glyph `glyph_id` scaled to `size`, if it has any
+    pub fn bitmap(
+        &mut self,
+        font_id: usize,
+        font: &Font,
+        glyph_id: u32,
+        size: f32,
+    ) -> Option<&ColorBitmap> {
+        self.font(font_id, font)?;
+        let key = (font_id, glyph_id, size.to_bits());
+        if !self.bitmaps.contains_key(&key) {
+            if self.size > BITMAPS_CAPACITY {
+                self.bitmaps.clear();
+                self.size = 0;
+            }
+            let bitmap = self.fonts[&font_id]
+                .as_ref()
+                .and_then(|color_font| color_font.bitmap(glyph_id, size));
+            self.size += bitmap.as_ref().map_or(0, |bitmap| bitmap.data.len() * 4);
+            self.bitmaps.insert(key, bitmap);
+        }
+        self.bitmaps[&key].as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{DynamicImage, ImageOutputFormat, Rgba, RgbaImage};
+
+    fn u16s(data: &mut Vec<u8>, values: &[u16]) {
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn u32s(data: &mut Vec<u8>, values: &[u32]) {
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    /// PNG of `width` by `height` opaque red pixels
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255]));
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        png
+    }
+
+    fn font(
+        cbdt: Option<(Vec<u8>, Vec<u8>)>,
+        sbix: Option<(Vec<u8>, u16)>,
+        colr: Option<Vec<u8>>,
+        palette: Vec<(u8, u8, u8, u8)>,
+    ) -> ColorFont {
+        ColorFont {
+            cbdt: cbdt.map(|(cblc, cbdt)| (cblc.into(), cbdt.into())),
+            sbix: sbix.map(|(sbix, count)| (sbix.into(), count)),
+            colr: colr.map(Vec::into),
+            palette,
+        }
+    }
+
+    /// `COLR` table with glyph 3 drawn by glyph 30, and glyph 7 by
+    /// glyphs 10 and 11
+    fn colr() -> Vec<u8> {
+        let mut colr = Vec::new();
+        u16s(&mut colr, &[0, 2]);
+        u32s(&mut colr, &[14, 26]);
+        u16s(&mut colr, &[3]);
+        // Base glyph records, then layer records
+        u16s(&mut colr, &[3, 0, 1, 7, 1, 2]);
+        u16s(&mut colr, &[30, 0, 10, 1, 11, FOREGROUND_PALETTE_INDEX]);
+        colr
+    }
+
+    /// `CPAL` table with a red and a translucent blue color
+    fn cpal() -> Vec<u8> {
+        let mut cpal = Vec::new();
+        u16s(&mut cpal, &[0, 2, 1, 2]);
+        u32s(&mut cpal, &[14]);
+        u16s(&mut cpal, &[0]);
+        cpal.extend_from_slice(&[0, 0, 255, 255, 255, 0, 0, 128]);
+        cpal
+    }
+
+    /// `CBLC` and `CBDT` tables with a 20 pixels per em strike holding
+    /// glyph 5, stored with index format 1 and image format 17
+    fn cbdt(png: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut cblc = Vec::new();
+        u32s(&mut cblc, &[0x0003_0000, 1]);
+        // Bitmap size record, with the line metrics left empty
+        u32s(&mut cblc, &[56, 20, 1, 0]);
+        cblc.extend_from_slice(&[0; 24]);
+        u16s(&mut cblc, &[5, 5]);
+        cblc.extend_from_slice(&[20, 20, 32, 1]);
+        // Index subtable array, then the subtable
+        u16s(&mut cblc, &[5, 5]);
+        u32s(&mut cblc, &[8]);
+        u16s(&mut cblc, &[1, 17]);
+        u32s(&mut cblc, &[4, 0, 9 + png.len() as u32]);
+
+        let mut cbdt = Vec::new();
+        u32s(&mut cbdt, &[0x0003_0000]);
+        // Small metrics: height, width, bearings and advance
+        cbdt.extend_from_slice(&[2, 2, 1, 3, 2]);
+        u32s(&mut cbdt, &[png.len() as u32]);
+        cbdt.extend_from_slice(png);
+        (cblc, cbdt)
+    }
+
+    /// `sbix` table of a font with 2 glyphs, with a 10 pixels per em strike
+    /// holding glyph 1
+    fn sbix(png: &[u8]) -> Vec<u8> {
+        let mut sbix = Vec::new();
+        u16s(&mut sbix, &[1, 1]);
+        u32s(&mut sbix, &[1, 12]);
+        // Strike, with offsets from its start
+        u16s(&mut sbix, &[10, 72]);
+        u32s(&mut sbix, &[16, 16, 24 + png.len() as u32]);
+        u16s(&mut sbix, &[2, 4]);
+        sbix.extend_from_slice(b"png ");
+        sbix.extend_from_slice(png);
+        sbix
+    }
+
+    #[test]
+    fn reads_colr_layers_colored_with_the_palette() {
+        let palette = read_palette(&cpal()).unwrap();
+        assert_eq!(palette, vec![(255, 255, 0, 0), (128, 0, 0, 255)]);
+
+        let font = font(None, None, Some(colr()), palette);
+        let layers = font.layers(7).unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].glyph_id, 10);
+        assert_eq!(layers[0].color, Some((128, 0, 0, 255)));
+        assert_eq!(layers[1].glyph_id, 11);
+        assert_eq!(layers[1].color, None);
+        assert_eq!(font.layers(3).unwrap()[0].glyph_id, 30);
+        assert!(font.layers(5).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_colr_and_cpal_tables() {
+        let colr = colr();
+        for length in 0..colr.len() {
+            let font = font(None, None, Some(colr[..length].to_vec()), Vec::new());
+            assert!(font.layers(7).is_none());
+        }
+        let cpal = cpal();
+        for length in 0..cpal.len() {
+            assert!(read_palette(&cpal[..length]).is_none());
+        }
+
+        // Base glyph records past the end of the table
+        let mut records = colr.clone();
+        records[2..4].copy_from_slice(&100u16.to_be_bytes());
+        assert!(font(None, None, Some(records), Vec::new()).layers(90).is_none());
+        // Layer records past the end of the table
+        let mut layers = colr.clone();
+        layers[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(font(None, None, Some(layers), Vec::new()).layers(7).is_none());
+        // Palette indices past the end of the palette use the text color
+        let font = font(None, None, Some(colr), Vec::new());
+        assert_eq!(font.layers(7).unwrap()[0].color, None);
+    }
+
+    #[test]
+    fn scales_cbdt_bitmaps_to_the_font_size() {
+        let font = font(Some(cbdt(&png(2, 2))), None, None, Vec::new());
+        let bitmap = font.bitmap(5, 40.).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (4, 4));
+        assert_eq!((bitmap.left, bitmap.top), (2., -6.));
+        assert_eq!(bitmap.data.len(), 16);
+        assert!(font.bitmap(4, 40.).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_cblc_and_cbdt_tables() {
+        let (cblc, cbdt) = cbdt(&png(2, 2));
+        // The offset past the last glyph is never read
+        for length in 0..cblc.len() - 4 {
+            let tables = Some((cblc[..length].to_vec(), cbdt.clone()));
+            assert!(font(tables, None, None, Vec::new()).bitmap(5, 20.).is_none());
+        }
+        for length in 0..cbdt.len() {
+            let tables = Some((cblc.clone(), cbdt[..length].to_vec()));
+            assert!(font(tables, None, None, Vec::new()).bitmap(5, 20.).is_none());
+        }
+
+        let with = |offset: usize, bytes: &[u8]| {
+            let mut cblc = cblc.clone();
+            cblc[offset..offset + bytes.len()].copy_from_slice(bytes);
+            font(Some((cblc, cbdt.clone())), None, None, Vec::new())
+        };
+        // Image data past the end of the `CBDT` table
+        assert!(with(68, &u32::MAX.to_be_bytes()).bitmap(5, 20.).is_none());
+        // Index subtable past the end of the `CBLC` table
+        assert!(with(60, &u32::MAX.to_be_bytes()).bitmap(5, 20.).is_none());
+        // Empty strikes cannot be scaled
+        assert!(with(52, &[0, 0]).bitmap(5, 20.).is_none());
+        // Unknown index and image formats
+        assert!(with(64, &[0, 9]).bitmap(5, 20.).is_none());
+        assert!(with(66, &[0, 9]).bitmap(5, 20.).is_none());
+        // Format 4 subtables counting more pairs than the table holds
+        let mut pairs = with(64, &[0, 4]);
+        let (cblc, _) = pairs.cbdt.as_mut().unwrap();
+        cblc[72..76].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(pairs.bitmap(5, 20.).is_none());
+    }
+
+    #[test]
+    fn places_sbix_bitmaps_above_their_origin() {
+        let font = font(None, Some((sbix(&png(2, 2)), 2)), None, Vec::new());
+        let bitmap = font.bitmap(1, 20.).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (4, 4));
+        assert_eq!((bitmap.left, bitmap.top), (4., -12.));
+        // Glyph 0 has no data, and glyph 2 is not in the font
+        assert!(font.bitmap(0, 20.).is_none());
+        assert!(font.bitmap(2, 20.).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_sbix_tables() {
+        let sbix = sbix(&png(2, 2));
+        for length in 0..sbix.len() {
+            let font = font(None, Some((sbix[..length].to_vec(), 2)), None, Vec::new());
+            assert!(font.bitmap(1, 20.).is_none());
+        }
+
+        let with = |offset: usize, bytes: &[u8]| {
+            let mut sbix = sbix.clone();
+            sbix[offset..offset + bytes.len()].copy_from_slice(bytes);
+            font(None, Some((sbix, 2)), None, Vec::new())
+        };
+        // Strike past the end of the table
+        assert!(with(8, &u32::MAX.to_be_bytes()).bitmap(1, 20.).is_none());
+        // Glyph data ending before it starts
+        assert!(with(24, &[0, 0, 0, 8]).bitmap(1, 20.).is_none());
+        // Glyph data past the end of the table
+        assert!(with(24, &u32::MAX.to_be_bytes()).bitmap(1, 20.).is_none());
+        // Graphics other than PNG
+        assert!(with(32, b"jpg ").bitmap(1, 20.).is_none());
+        // Empty strikes cannot be scaled
+        assert!(with(12, &[0, 0]).bitmap(1, 20.).is_none());
+    }
+}
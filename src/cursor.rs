@@ -0,0 +1,161 @@
+//! Shape of the mouse cursor over the window
+//!
+//! The cursor is either set explicitly, such as by a message holding the
+//! display's [`CursorControl`], or taken from the regions of a [`CursorMap`]
+//! under the mouse.
+
+use crate::geometry::Rect;
+
+use hyber::util::Vector2D;
+
+use std::collections::BTreeMap;
+
+/// Shapes of the mouse cursor
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cursor {
+    Arrow,
+    /// Over something that can be clicked, such as a button
+    Hand,
+    /// Over something being dragged, such as a slider's thumb
+    Grabbing,
+    /// Over editable text
+    IBeam,
+    Crosshair,
+    ResizeHorizontal,
+    ResizeVertical,
+    ResizeAll,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor::Arrow
+    }
+}
+
+impl Cursor {
+    /// Gets the closest cursor style of [`minifb`](`crate`)
+    pub fn style(self) -> minifb::CursorStyle {
+        match self {
+            Cursor::Arrow => minifb::CursorStyle::Arrow,
+            Cursor::Hand => minifb::CursorStyle::OpenHand,
+            Cursor::Grabbing => minifb::CursorStyle::ClosedHand,
+            Cursor::IBeam => minifb::CursorStyle::Ibeam,
+            Cursor::Crosshair => minifb::CursorStyle::Crosshair,
+            Cursor::ResizeHorizontal => minifb::CursorStyle::ResizeLeftRight,
+            Cursor::ResizeVertical => minifb::CursorStyle::ResizeUpDown,
+            Cursor::ResizeAll => minifb::CursorStyle::ResizeAll,
+        }
+    }
+}
+
+/// Cursors of regions of the window, in logical units
+///
+/// Regions are identified by a key, such as the widget's key in the
+/// [`RenderInstructionCollection`](hyber::renderer::RenderInstructionCollection),
+/// and where they overlap the one with the highest key wins.
+#[derive(Default)]
+pub struct CursorMap {
+    regions: BTreeMap<usize, (Rect, Cursor)>,
+}
+
+impl CursorMap {
+    pub fn new() -> Self {
+        CursorMap::default()
+    }
+
+    /// Shows `cursor` over the area of `size` at `point`, replacing the
+    /// previous region of `key`
+    pub fn set_region(&mut self, key: usize, point: &Vector2D, size: &Vector2D, cursor: Cursor) {
+        self.regions.insert(key, (Rect::from_point_size(point, size), cursor));
+    }
+
+    pub fn remove_region(&mut self, key: usize) {
+        self.regions.remove(&key);
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Gets the cursor of the topmost region at (`x`, `y`), if any
+    pub fn cursor_at(&self, x: f32, y: f32) -> Option<Cursor> {
+        let (x, y) = (x.floor() as i32, y.floor() as i32);
+        self.regions
+            .values()
+            .rev()
+            .find(|(rect, _)| rect.contains_point(x, y))
+            .map(|(_, cursor)| *cursor)
+    }
+}
+
+/// Cursor of a window, shared with whatever needs to change it
+#[derive(Default)]
+pub struct CursorControl {
+    /// Cursor set explicitly, shown instead of the one of the map
+    requested: Option<Cursor>,
+    /// Cursors shown as the mouse moves over the regions of the window
+    pub map: CursorMap,
+}
+
+impl CursorControl {
+    /// Shows `cursor` wherever the mouse is, until [`CursorControl::reset`]
+    pub fn set(&mut self, cursor: Cursor) {
+        self.requested = Some(cursor);
+    }
+
+    /// Goes back to the cursors of the map
+    pub fn reset(&mut self) {
+        self.requested = None;
+    }
+
+    /// Gets the cursor to show with the mouse at (`x`, `y`), in logical units
+    pub fn cursor_at(&self, x: f32, y: f32) -> Cursor {
+        self.requested
+            .or_else(|| self.map.cursor_at(x, y))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(map: &mut CursorMap, key: usize, x: f64, width: f64, cursor: Cursor) {
+        let point = Vector2D::new(x, 0.);
+        let size = Vector2D::new(width, 10.);
+        map.set_region(key, &point, &size, cursor);
+    }
+
+    #[test]
+    fn highest_key_wins_where_regions_overlap() {
+        let mut map = CursorMap::new();
+        region(&mut map, 2, 0., 100., Cursor::Hand);
+        region(&mut map, 5, 50., 10., Cursor::IBeam);
+        assert_eq!(map.cursor_at(55., 5.), Some(Cursor::IBeam));
+        assert_eq!(map.cursor_at(20., 5.), Some(Cursor::Hand));
+        assert_eq!(map.cursor_at(20., 50.), None);
+    }
+
+    #[test]
+    fn replaces_and_removes_regions() {
+        let mut map = CursorMap::new();
+        region(&mut map, 1, 0., 10., Cursor::Hand);
+        region(&mut map, 1, 20., 10., Cursor::Crosshair);
+        assert_eq!(map.cursor_at(5., 5.), None);
+        assert_eq!(map.cursor_at(25., 5.), Some(Cursor::Crosshair));
+        map.remove_region(1);
+        assert_eq!(map.cursor_at(25., 5.), None);
+    }
+
+    #[test]
+    fn requested_cursor_overrides_the_map() {
+        let mut control = CursorControl::default();
+        region(&mut control.map, 0, 0., 10., Cursor::Hand);
+        assert_eq!(control.cursor_at(5., 5.), Cursor::Hand);
+        assert_eq!(control.cursor_at(50., 5.), Cursor::Arrow);
+        control.set(Cursor::Grabbing);
+        assert_eq!(control.cursor_at(50., 5.), Cursor::Grabbing);
+        control.reset();
+        assert_eq!(control.cursor_at(5., 5.), Cursor::Hand);
+    }
+}
@@ -0,0 +1,252 @@
+//! Tracking of the regions of the display that changed between frames
+//!
+//! The instructions of each widget key of a [`RenderInstructionCollection`]
+//! are hashed every frame. The keys whose hash changed damage both the area
+//! their instructions covered on the previous frame and the area they cover
+//! now, and only that area needs to be painted again.
+
+use crate::geometry::{hash_instructions, instruction_bounds, Rect};
+
+use hyber::renderer::{RenderInstruction, RenderInstructionCollection};
+
+use std::collections::{HashMap, HashSet};
+
+/// Area of the display to paint again
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Damage {
+    /// Nothing changed since the previous frame
+    None,
+    /// Only the pixels within the rectangle changed
+    Region(Rect),
+    /// The whole display needs to be painted
+    Full,
+}
+
+impl Damage {
+    /// Adds the pixels of `bounds` to the damage, where `None` stands
+    /// for the whole display
+    fn add(&mut self, bounds: Option<Rect>) {
+        *self = match (*self, bounds) {
+            (Damage::Full, _) | (_, None) => Damage::Full,
+            (_, Some(rect)) if rect.is_empty() => *self,
+            (Damage::None, Some(rect)) => Damage::Region(rect),
+            (Damage::Region(region), Some(rect)) => Damage::Region(region.union(&rect)),
+        };
+    }
+}
+
+/// Bounds, in physical pixels, of a list of instructions given in logical
+/// units, where `None` stands for the whole display
+fn bounds(instructions: &[RenderInstruction], scale: f64) -> Option<Rect> {
+    let mut bounds = Rect::new(0, 0, 0, 0);
+    for instruction in instructions {
+        bounds = bounds.union(&instruction_bounds(instruction, 1.)?.scale(scale));
+    }
+    Some(bounds)
+}
+
+/// Hashes and bounds of the instructions drawn on the previous frame
+#[derive(Default)]
+pub struct DamageTracker {
+    /// Hash and bounds of the instructions of each key
+    previous: HashMap<usize, (u64, Option<Rect>)>,
+    /// Keys whose instructions changed on the last update
+    changed: Vec<usize>,
+    /// Whether the next update must damage the whole display
+    invalidated: bool,
+    /// Keys treated as changed on the next update, even if their
+    /// instructions are the same
+    stale: HashSet<usize>,
+    /// Scale factor the instructions, given in logical units, are drawn at
+    scale: f64,
+}
+
+impl DamageTracker {
+    pub fn new() -> Self {
+        DamageTracker {
+            invalidated: true,
+            scale: 1.,
+            ..DamageTracker::default()
+        }
+    }
+
+    /// Changes the scale factor the instructions are drawn at, which
+    /// damages the whole display
+    pub fn set_scale(&mut self, scale: f64) {
+        if self.scale != scale {
+            self.scale = scale;
+            self.invalidate();
+        }
+    }
+
+    /// Makes the next update damage the whole display, such as after
+    /// the draw target was replaced
+    pub fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+
+    /// Makes the next update damage the area of `key`, such as after
+    /// something drawn with its instructions changed
+    pub fn invalidate_key(&mut self, key: usize) {
+        self.stale.insert(key);
+    }
+
+    /// Keys added, removed or whose instructions changed on the last update
+    pub fn changed_keys(&self) -> &[usize] {
+        &self.changed
+    }
+
+    /// Compares `collection` with the previous frame, returning the damaged
+    /// area, and remembers it as the new previous frame
+    pub fn update(&mut self, collection: &RenderInstructionCollection) -> Damage {
+        let mut damage = if self.invalidated {
+            Damage::Full
+        } else {
+            Damage::None
+        };
+        self.invalidated = false;
+        self.changed.clear();
+
+        let mut current = HashMap::with_capacity(collection.pairs.len());
+        for (key, instructions) in collection.pairs.iter() {
+            let hash = hash_instructions(instructions);
+            let entry = match self.previous.remove(key) {
+                Some((previous_hash, previous_bounds))
+                    if previous_hash == hash && !self.stale.contains(key) =>
+                {
+                    (hash, previous_bounds)
+                }
+                previous => {
+                    let new_bounds = bounds(instructions, self.scale);
+                    if let Some((_, previous_bounds)) = previous {
+                        damage.add(previous_bounds);
+                    }
+                    damage.add(new_bounds);
+                    self.changed.push(*key);
+                    (hash, new_bounds)
+                }
+            };
+            current.insert(*key, entry);
+        }
+
+        // Whatever is left was drawn by keys that no longer exist
+        for (key, (_, previous_bounds)) in self.previous.drain() {
+            damage.add(previous_bounds);
+            self.changed.push(key);
+        }
+        self.previous = current;
+        self.stale.clear();
+
+        damage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyber::util::{Color, Vector2D};
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> RenderInstruction {
+        RenderInstruction::DrawRect {
+            point: Vector2D::new(x, y),
+            size: Vector2D::new(width, height),
+            color: Color::new(255, 0, 0, 0),
+            clip_point: Vector2D::new(0., 0.),
+            clip_size: Vector2D::new(800., 600.),
+        }
+    }
+
+    fn collection(pairs: Vec<(usize, RenderInstruction)>) -> RenderInstructionCollection {
+        let mut collection = RenderInstructionCollection::new();
+        for (key, instruction) in pairs {
+            collection.pairs.insert(key, vec![instruction]);
+        }
+        collection
+    }
+
+    #[test]
+    fn damages_everything_on_the_first_frame() {
+        let mut tracker = DamageTracker::new();
+        let frame = collection(vec![(0, rect(10., 10., 20., 20.))]);
+        assert_eq!(tracker.update(&frame), Damage::Full);
+        assert_eq!(tracker.update(&frame), Damage::None);
+        assert!(tracker.changed_keys().is_empty());
+    }
+
+    #[test]
+    fn merges_the_old_and_new_bounds_of_changed_keys() {
+        let mut tracker = DamageTracker::new();
+        tracker.update(&collection(vec![
+            (0, rect(10., 10., 20., 20.)),
+            (1, rect(300., 300., 10., 10.)),
+        ]));
+        let damage = tracker.update(&collection(vec![
+            (0, rect(50., 40., 20., 20.)),
+            (1, rect(300., 300., 10., 10.)),
+        ]));
+        // Bounds are grown by a pixel for the antialiased edges
+        assert_eq!(damage, Damage::Region(Rect::new(9, 9, 71, 61)));
+        assert_eq!(tracker.changed_keys(), &[0]);
+    }
+
+    #[test]
+    fn damages_the_bounds_of_removed_keys() {
+        let mut tracker = DamageTracker::new();
+        tracker.update(&collection(vec![
+            (0, rect(10., 10., 20., 20.)),
+            (1, rect(100., 100., 10., 10.)),
+        ]));
+        let damage = tracker.update(&collection(vec![(0, rect(10., 10., 20., 20.))]));
+        assert_eq!(damage, Damage::Region(Rect::new(99, 99, 111, 111)));
+        assert_eq!(tracker.changed_keys(), &[1]);
+    }
+
+    #[test]
+    fn damages_everything_for_unbounded_instructions() {
+        let mut tracker = DamageTracker::new();
+        tracker.update(&collection(vec![(0, rect(10., 10., 20., 20.))]));
+        let damage = tracker.update(&collection(vec![(
+            0,
+            RenderInstruction::Clear {
+                color: Color::new(255, 0, 0, 0),
+            },
+        )]));
+        assert_eq!(damage, Damage::Full);
+    }
+
+    #[test]
+    fn invalidating_damages_everything_once() {
+        let mut tracker = DamageTracker::new();
+        let frame = collection(vec![(0, rect(10., 10., 20., 20.))]);
+        tracker.update(&frame);
+        tracker.invalidate();
+        assert_eq!(tracker.update(&frame), Damage::Full);
+        assert_eq!(tracker.update(&frame), Damage::None);
+        tracker.set_scale(2.);
+        assert_eq!(tracker.update(&frame), Damage::Full);
+    }
+
+    #[test]
+    fn damages_invalidated_keys() {
+        let mut tracker = DamageTracker::new();
+        let frame = collection(vec![
+            (0, rect(10., 10., 20., 20.)),
+            (1, rect(100., 100., 10., 10.)),
+        ]);
+        tracker.update(&frame);
+        tracker.invalidate_key(1);
+        assert_eq!(tracker.update(&frame), Damage::Region(Rect::new(99, 99, 111, 111)));
+        assert_eq!(tracker.changed_keys(), &[1]);
+        assert_eq!(tracker.update(&frame), Damage::None);
+    }
+
+    #[test]
+    fn scales_the_damage_to_physical_pixels() {
+        let mut tracker = DamageTracker::new();
+        tracker.set_scale(2.);
+        tracker.update(&collection(vec![(0, rect(10., 10., 20., 20.))]));
+        let damage = tracker.update(&collection(vec![(0, rect(10., 10., 20., 21.))]));
+        assert_eq!(damage, Damage::Region(Rect::new(18, 18, 62, 64)));
+    }
+}
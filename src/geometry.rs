@@ -0,0 +1,604 @@
+//! Bounds and hashes of the render instructions of [`hyber`](`crate`)
+
+use hyber::renderer::{DrawImageOptions, RenderInstruction};
+use hyber::util::{Color, Vector2D};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Half of the width of the lines drawn by [`RenderInstruction::DrawLine`],
+/// including their square caps, at a scale factor of 1
+const LINE_EXTENT: f64 = 8.;
+
+/// Rectangle of whole pixels, from its left and top edges (inclusive)
+/// to its right and bottom edges (exclusive)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// Smallest rectangle of whole pixels covering the area of `size` at `point`
+    pub fn from_point_size(point: &Vector2D, size: &Vector2D) -> Self {
+        Rect::from_edges(point.x, point.y, point.x + size.x, point.y + size.y)
+    }
+
+    /// Smallest rectangle of whole pixels covering the given edges
+    pub fn from_edges(left: f64, top: f64, right: f64, bottom: f64) -> Self {
+        Rect {
+            left: left.floor() as i32,
+            top: top.floor() as i32,
+            right: right.ceil() as i32,
+            bottom: bottom.ceil() as i32,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        (self.right - self.left).max(0)
+    }
+
+    pub fn height(&self) -> i32 {
+        (self.bottom - self.top).max(0)
+    }
+
+    /// Whether the rectangle covers no pixels
+    pub fn is_empty(&self) -> bool {
+        self.right <= self.left || self.bottom <= self.top
+    }
+
+    /// Smallest rectangle covering both rectangles
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Rect {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// Pixels covered by both rectangles
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        Rect {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+
+    /// Whether both rectangles share at least a pixel
+    pub fn intersects(&self, other: &Rect) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    /// Whether every pixel of `other` is within the rectangle
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.is_empty()
+            || (self.left <= other.left
+                && self.top <= other.top
+                && self.right >= other.right
+                && self.bottom >= other.bottom)
+    }
+
+    /// Whether the pixel at (`x`, `y`) is within the rectangle
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    /// Smallest rectangle of whole pixels covering the rectangle with its
+    /// edges multiplied by `scale`
+    pub fn scale(&self, scale: f64) -> Rect {
+        Rect::from_edges(
+            self.left as f64 * scale,
+            self.top as f64 * scale,
+            self.right as f64 * scale,
+            self.bottom as f64 * scale,
+        )
+    }
+
+    /// Grows the rectangle by `amount` pixels on each side
+    pub fn inflate(&self, amount: i32) -> Rect {
+        Rect {
+            left: self.left - amount,
+            top: self.top - amount,
+            right: self.right + amount,
+            bottom: self.bottom + amount,
+        }
+    }
+}
+
+/// Gets the pixels a render instruction may paint, or `None` when it
+/// paints the whole draw target
+///
+/// The bounds never go beyond the instruction's clip, and are grown by a
+/// pixel to hold the antialiased edges. Lines are as wide as drawn at a
+/// scale factor of `scale`.
+pub fn instruction_bounds(instruction: &RenderInstruction, scale: f64) -> Option<Rect> {
+    let (shape, clip_point, clip_size) = match instruction {
+        RenderInstruction::Clear { .. } => return None,
+        RenderInstruction::DrawPoint {
+            point,
+            clip_point,
+            clip_size,
+            ..
+        } => (
+            Some(Rect::from_edges(point.x, point.y, point.x + 1., point.y + 1.)),
+            clip_point,
+            clip_size,
+        ),
+        RenderInstruction::DrawLine {
+            point_a,
+            point_b,
+            clip_point,
+            clip_size,
+            ..
+        } => (
+            Some(Rect::from_edges(
+                point_a.x.min(point_b.x) - LINE_EXTENT * scale,
+                point_a.y.min(point_b.y) - LINE_EXTENT * scale,
+                point_a.x.max(point_b.x) + LINE_EXTENT * scale,
+                point_a.y.max(point_b.y) + LINE_EXTENT * scale,
+            )),
+            clip_point,
+            clip_size,
+        ),
+        RenderInstruction::DrawArc {
+            point,
+            r,
+            clip_point,
+            clip_size,
+            ..
+        }
+        | RenderInstruction::DrawCircle {
+            point,
+            r,
+            clip_point,
+            clip_size,
+            ..
+        } => {
+            let r = *r as f64;
+            (
+                Some(Rect::from_edges(point.x - r, point.y - r, point.x + r, point.y + r)),
+                clip_point,
+                clip_size,
+            )
+        }
+        RenderInstruction::DrawRect {
+            point,
+            size,
+            clip_point,
+            clip_size,
+            ..
+        } => (Some(Rect::from_point_size(point, size)), clip_point, clip_size),
+        RenderInstruction::DrawTriangle {
+            point_a,
+            point_b,
+            point_c,
+            clip_point,
+            clip_size,
+            ..
+        } => (
+            Some(Rect::from_edges(
+                point_a.x.min(point_b.x).min(point_c.x),
+                point_a.y.min(point_b.y).min(point_c.y),
+                point_a.x.max(point_b.x).max(point_c.x),
+                point_a.y.max(point_b.y).max(point_c.y),
+            )),
+            clip_point,
+            clip_size,
+        ),
+        RenderInstruction::DrawImage {
+            point,
+            options,
+            clip_point,
+            clip_size,
+            ..
+        } => (
+            match options {
+                DrawImageOptions::Resize { width, height } => Some(Rect::from_edges(
+                    point.x,
+                    point.y,
+                    point.x + *width as f64,
+                    point.y + *height as f64,
+                )),
+                // The size of the image is only known once it is loaded
+                _ => None,
+            },
+            clip_point,
+            clip_size,
+        ),
+        // Glyphs may go beyond any box derived from the font size
+        RenderInstruction::DrawText {
+            clip_point,
+            clip_size,
+            ..
+        } => (None, clip_point, clip_size),
+    };
+
+    let clip = Rect::from_point_size(clip_point, clip_size);
+    Some(match shape {
+        Some(shape) => shape.inflate(1).intersection(&clip),
+        None => clip,
+    })
+}
+
+pub(crate) fn scale_vector(vector: &Vector2D, scale: f64) -> Vector2D {
+    Vector2D::new(vector.x * scale, vector.y * scale)
+}
+
+fn scale_size(size: usize, scale: f64) -> usize {
+    (size as f64 * scale).round() as usize
+}
+
+/// Gets a render instruction given in logical units in physical pixels,
+/// for a display with a scale factor of `scale`
+pub fn scale_instruction(instruction: &RenderInstruction, scale: f64) -> RenderInstruction {
+    match instruction {
+        RenderInstruction::Clear { color } => RenderInstruction::Clear {
+            color: color.clone(),
+        },
+        RenderInstruction::DrawPoint {
+            point,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawPoint {
+            point: scale_vector(point, scale),
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawLine {
+            point_a,
+            point_b,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawLine {
+            point_a: scale_vector(point_a, scale),
+            point_b: scale_vector(point_b, scale),
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawArc {
+            point,
+            r,
+            s_ang,
+            e_ang,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawArc {
+            point: scale_vector(point, scale),
+            r: scale_size(*r, scale),
+            s_ang: *s_ang,
+            e_ang: *e_ang,
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawCircle {
+            point,
+            r,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawCircle {
+            point: scale_vector(point, scale),
+            r: scale_size(*r, scale),
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawRect {
+            point,
+            size,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawRect {
+            point: scale_vector(point, scale),
+            size: scale_vector(size, scale),
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawTriangle {
+            point_a,
+            point_b,
+            point_c,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawTriangle {
+            point_a: scale_vector(point_a, scale),
+            point_b: scale_vector(point_b, scale),
+            point_c: scale_vector(point_c, scale),
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawImage {
+            point,
+            path,
+            options,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawImage {
+            point: scale_vector(point, scale),
+            path: path.clone(),
+            options: match options {
+                // The multiplier is a float, so fractional scales are kept
+                DrawImageOptions::OriginalSize => {
+                    DrawImageOptions::ResizeMultiplyer { mult: scale as _ }
+                }
+                DrawImageOptions::Resize { width, height } => DrawImageOptions::Resize {
+                    width: (*width as f64 * scale).round() as _,
+                    height: (*height as f64 * scale).round() as _,
+                },
+                DrawImageOptions::ResizeMultiplyer { mult } => DrawImageOptions::ResizeMultiplyer {
+                    mult: (*mult as f64 * scale) as _,
+                },
+            },
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+        RenderInstruction::DrawText {
+            point,
+            font_size,
+            string,
+            color,
+            clip_point,
+            clip_size,
+        } => RenderInstruction::DrawText {
+            point: scale_vector(point, scale),
+            font_size: scale_size(*font_size, scale),
+            string: string.clone(),
+            color: color.clone(),
+            clip_point: scale_vector(clip_point, scale),
+            clip_size: scale_vector(clip_size, scale),
+        },
+    }
+}
+
+fn hash_vector<H: Hasher>(vector: &Vector2D, state: &mut H) {
+    vector.x.to_bits().hash(state);
+    vector.y.to_bits().hash(state);
+}
+
+fn hash_color<H: Hasher>(color: &Color, state: &mut H) {
+    (color.a, color.r, color.g, color.b).hash(state);
+}
+
+/// Feeds every field of a render instruction to `state`
+pub fn hash_instruction<H: Hasher>(instruction: &RenderInstruction, state: &mut H) {
+    match instruction {
+        RenderInstruction::Clear { color } => {
+            0u8.hash(state);
+            hash_color(color, state);
+        }
+        RenderInstruction::DrawPoint {
+            point,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            1u8.hash(state);
+            hash_vector(point, state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawLine {
+            point_a,
+            point_b,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            2u8.hash(state);
+            hash_vector(point_a, state);
+            hash_vector(point_b, state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawArc {
+            point,
+            r,
+            s_ang,
+            e_ang,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            3u8.hash(state);
+            hash_vector(point, state);
+            (*r, *s_ang, *e_ang).hash(state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawCircle {
+            point,
+            r,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            4u8.hash(state);
+            hash_vector(point, state);
+            r.hash(state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawRect {
+            point,
+            size,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            5u8.hash(state);
+            hash_vector(point, state);
+            hash_vector(size, state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawTriangle {
+            point_a,
+            point_b,
+            point_c,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            6u8.hash(state);
+            hash_vector(point_a, state);
+            hash_vector(point_b, state);
+            hash_vector(point_c, state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawImage {
+            point,
+            path,
+            options,
+            clip_point,
+            clip_size,
+        } => {
+            7u8.hash(state);
+            hash_vector(point, state);
+            path.hash(state);
+            match options {
+                DrawImageOptions::OriginalSize => 0u8.hash(state),
+                DrawImageOptions::Resize { width, height } => {
+                    1u8.hash(state);
+                    (*width as f64).to_bits().hash(state);
+                    (*height as f64).to_bits().hash(state);
+                }
+                DrawImageOptions::ResizeMultiplyer { mult } => {
+                    2u8.hash(state);
+                    (*mult as f64).to_bits().hash(state);
+                }
+            }
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+        RenderInstruction::DrawText {
+            point,
+            font_size,
+            string,
+            color,
+            clip_point,
+            clip_size,
+        } => {
+            8u8.hash(state);
+            hash_vector(point, state);
+            font_size.hash(state);
+            string.hash(state);
+            hash_color(color, state);
+            hash_vector(clip_point, state);
+            hash_vector(clip_size, state);
+        }
+    }
+}
+
+/// Hash of a list of render instructions, in order
+pub fn hash_instructions(instructions: &[RenderInstruction]) -> u64 {
+    let mut state = DefaultHasher::new();
+    instructions.len().hash(&mut state);
+    for instruction in instructions {
+        hash_instruction(instruction, &mut state);
+    }
+    state.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_fractional_edges() {
+        assert_eq!(Rect::from_edges(0.5, 1.2, 9.1, 10.), Rect::new(0, 1, 10, 10));
+    }
+
+    #[test]
+    fn unites_and_intersects_rects() {
+        let a = Rect::new(0, 0, 10, 10);
+        let b = Rect::new(5, 5, 20, 15);
+        assert_eq!(a.union(&b), Rect::new(0, 0, 20, 15));
+        assert_eq!(a.intersection(&b), Rect::new(5, 5, 10, 10));
+        assert!(a.intersects(&b));
+        // Rects sharing only an edge have no pixel in common
+        assert!(!a.intersects(&Rect::new(10, 0, 20, 10)));
+    }
+
+    #[test]
+    fn ignores_empty_rects_in_unions() {
+        let a = Rect::new(5, 5, 10, 10);
+        let empty = Rect::new(100, 100, 100, 120);
+        assert!(empty.is_empty());
+        assert_eq!(a.union(&empty), a);
+        assert_eq!(empty.union(&a), a);
+        assert!(a.contains(&empty));
+    }
+
+    #[test]
+    fn scales_rects_outward() {
+        assert_eq!(Rect::new(1, 2, 3, 5).scale(1.5), Rect::new(1, 3, 5, 8));
+    }
+
+    #[test]
+    fn keeps_fractional_image_scales() {
+        let image = RenderInstruction::DrawImage {
+            point: Vector2D::new(10., 10.),
+            path: String::from("rust.png"),
+            options: DrawImageOptions::OriginalSize,
+            clip_point: Vector2D::new(0., 0.),
+            clip_size: Vector2D::new(100., 100.),
+        };
+        match scale_instruction(&image, 1.5) {
+            RenderInstruction::DrawImage {
+                point,
+                options: DrawImageOptions::ResizeMultiplyer { mult },
+                ..
+            } => {
+                assert_eq!(mult as f64, 1.5);
+                assert_eq!(point.x, 15.);
+            }
+            _ => panic!("the image should be resized by the scale"),
+        }
+    }
+
+    #[test]
+    fn contains_rects_and_points() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert!(a.contains(&Rect::new(2, 2, 10, 10)));
+        assert!(!a.contains(&Rect::new(2, 2, 11, 10)));
+        assert!(a.contains_point(9, 0));
+        assert!(!a.contains_point(10, 0));
+    }
+}
@@ -0,0 +1,599 @@
+//! Cache of glyphs rasterized with [`font_kit`](`crate`)
+//!
+//! Glyphs are rasterized once into coverage masks, which are composited
+//! with the text color every time the glyph is drawn again. How they are
+//! rasterized is set by the [`TextRenderOptions`].
+
+use font_kit::canvas::{Canvas, Format, RasterizationOptions};
+use font_kit::font::Font;
+use font_kit::hinting::HintingOptions;
+
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2F;
+
+use raqote::{DrawTarget, Mask, SolidSource};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Number of positions a glyph can take within a pixel, on each axis
+const SUBPIXEL_STEPS: f32 = 4.;
+
+/// Default memory cap of a [`GlyphCache`], in bytes
+pub const DEFAULT_GLYPH_CACHE_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Lowest gamma of [`TextRenderOptions`], below which text vanishes
+pub const MIN_GAMMA: f32 = 0.1;
+
+/// Highest gamma of [`TextRenderOptions`], above which text is solid blocks
+pub const MAX_GAMMA: f32 = 10.;
+
+/// Hinting of the glyph outlines
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextHinting {
+    /// Outlines are left as designed
+    None,
+    /// Outlines are fitted to the pixel grid vertically
+    Vertical,
+    /// Outlines are fitted to the pixel grid on both axes
+    Full,
+}
+
+/// Order of the color subpixels of the screen, from left to right
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SubpixelOrder {
+    Rgb,
+    Bgr,
+}
+
+/// Antialiasing of the glyphs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextAntialias {
+    /// Each pixel is either fully covered or not covered at all
+    None,
+    /// Pixels are partially covered along the glyphs' edges
+    Grayscale,
+    /// Each color subpixel of an LCD screen is covered on its own
+    Subpixel(SubpixelOrder),
+}
+
+/// Options of the rasterization of text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextRenderOptions {
+    /// Hinting of the glyph outlines
+    pub hinting: TextHinting,
+    /// Antialiasing of the glyphs
+    pub antialias: TextAntialias,
+    /// Gamma applied to the glyphs' coverage, where values above 1
+    /// make text look heavier
+    pub gamma: f32,
+    /// Extra contrast of the glyphs' edges, from 0 (none) to 1
+    pub contrast: f32,
+}
+
+impl Default for TextRenderOptions {
+    fn default() -> Self {
+        TextRenderOptions {
+            hinting: TextHinting::None,
+            antialias: TextAntialias::Grayscale,
+            gamma: 1.,
+            contrast: 0.,
+        }
+    }
+}
+
+impl TextRenderOptions {
+    /// Gets the options with the gamma clamped between [`MIN_GAMMA`] and
+    /// [`MAX_GAMMA`], and the contrast between 0 and 1
+    ///
+    /// Gammas that are not positive finite numbers, and contrasts that are
+    /// not finite, are rejected.
+    pub fn clamped(self) -> Result<TextRenderOptions, InvalidTextRenderOptions> {
+        if !(self.gamma > 0. && self.gamma.is_finite()) || !self.contrast.is_finite() {
+            return Err(InvalidTextRenderOptions {
+                gamma: self.gamma,
+                contrast: self.contrast,
+            });
+        }
+        Ok(TextRenderOptions {
+            gamma: self.gamma.max(MIN_GAMMA).min(MAX_GAMMA),
+            contrast: self.contrast.max(0.).min(1.),
+            ..self
+        })
+    }
+
+    /// Builds the table mapping a glyph's coverage to the coverage
+    /// drawn, after the gamma and contrast adjustments
+    fn coverage_table(&self) -> [u8; 256] {
+        let mut table = [0; 256];
+        for (coverage, entry) in table.iter_mut().enumerate() {
+            let mut value = (coverage as f32 / 255.).powf(1. / self.gamma);
+            value += self.contrast * value * (1. - value);
+            *entry = (value.max(0.).min(1.) * 255.).round() as u8;
+        }
+        table
+    }
+}
+
+/// Gamma that is not a positive finite number, or contrast that is not
+/// finite, of [`TextRenderOptions`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InvalidTextRenderOptions {
+    pub gamma: f32,
+    pub contrast: f32,
+}
+
+impl fmt::Display for InvalidTextRenderOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the text gamma must be a positive number and the contrast a finite number, \
+             not {} and {}",
+            self.gamma, self.contrast
+        )
+    }
+}
+
+impl Error for InvalidTextRenderOptions {}
+
+/// Rasterized glyph, ready to be composited with the text color
+pub enum GlyphMask {
+    /// Coverage of each pixel
+    Coverage(Mask),
+    /// Coverage of each red, green and blue subpixel, in that order
+    Subpixel {
+        width: i32,
+        height: i32,
+        data: Vec<u8>,
+    },
+}
+
+impl GlyphMask {
+    /// Whether the mask covers no pixels at all
+    pub fn is_empty(&self) -> bool {
+        match self {
+            GlyphMask::Coverage(mask) => mask.width == 0 || mask.height == 0,
+            GlyphMask::Subpixel { width, height, .. } => *width == 0 || *height == 0,
+        }
+    }
+
+    /// Memory, in bytes, used by the mask
+    fn len(&self) -> usize {
+        match self {
+            GlyphMask::Coverage(mask) => mask.data.len(),
+            GlyphMask::Subpixel { data, .. } => data.len(),
+        }
+    }
+}
+
+/// Key identifying a rasterized glyph
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    /// Identifier of the font given by the [`FontCache`](crate::text::FontCache)
+    font_id: usize,
+    /// Bits of the font size
+    size: u32,
+    /// Identifier of the glyph within its font
+    glyph_id: u32,
+    /// Subpixel offset of the glyph, in steps of [`SUBPIXEL_STEPS`]
+    subpixel: (u8, u8),
+}
+
+/// Coverage mask of a rasterized glyph
+struct CachedGlyph {
+    /// Offset of the mask from the pixel holding the glyph's origin
+    offset: (i32, i32),
+    /// Coverage of the glyph
+    mask: GlyphMask,
+    /// Value of the cache's clock when the glyph was last drawn
+    last_used: u64,
+}
+
+/// Rasterized glyphs, limited to a memory cap
+///
+/// When the cap is exceeded, the least recently used glyphs are evicted.
+pub struct GlyphCache {
+    glyphs: HashMap<GlyphKey, CachedGlyph>,
+    /// Maximum memory, in bytes, used by the masks
+    capacity: usize,
+    /// Memory, in bytes, currently used by the masks
+    size: usize,
+    /// Counter increased on every lookup
+    clock: u64,
+    /// How the glyphs are rasterized
+    options: TextRenderOptions,
+    /// Coverage adjustments of the [`TextRenderOptions`]
+    coverage_table: [u8; 256],
+}
+
+impl GlyphCache {
+    pub fn new(capacity: usize) -> Self {
+        GlyphCache {
+            glyphs: HashMap::new(),
+            capacity,
+            size: 0,
+            clock: 0,
+            options: TextRenderOptions::default(),
+            coverage_table: TextRenderOptions::default().coverage_table(),
+        }
+    }
+
+    /// How the glyphs are rasterized
+    pub fn options(&self) -> TextRenderOptions {
+        self.options
+    }
+
+    /// Changes how the glyphs are rasterized, clamped as by
+    /// [`TextRenderOptions::clamped`], dropping the glyphs rasterized with
+    /// the previous options
+    ///
+    /// The current options are kept if the new ones are invalid.
+    pub fn set_options(
+        &mut self,
+        options: TextRenderOptions,
+    ) -> Result<(), InvalidTextRenderOptions> {
+        let options = options.clamped()?;
+        if options != self.options {
+            self.options = options;
+            self.coverage_table = options.coverage_table();
+            self.clear();
+        }
+        Ok(())
+    }
+
+    /// Maximum memory, in bytes, used by the cache
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the memory cap of the cache, evicting glyphs if needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(None);
+    }
+
+    /// Memory, in bytes, currently used by the cache
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Removes every glyph from the cache
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+        self.size = 0;
+    }
+
+    /// Gets the mask of glyph `glyph_id` whose origin is at `position`,
+    /// along with the pixel where the mask's top left corner goes
+    pub fn get(
+        &mut self,
+        font_id: usize,
+        font: &Font,
+        size: f32,
+        glyph_id: u32,
+        position: (f32, f32),
+    ) -> (i32, i32, &GlyphMask) {
+        let origin = (position.0.floor(), position.1.floor());
+        let subpixel = (
+            ((position.0 - origin.0) * SUBPIXEL_STEPS).floor() as u8,
+            ((position.1 - origin.1) * SUBPIXEL_STEPS).floor() as u8,
+        );
+        let key = GlyphKey {
+            font_id,
+            size: size.to_bits(),
+            glyph_id,
+            subpixel,
+        };
+
+        self.clock += 1;
+        if !self.glyphs.contains_key(&key) {
+            let glyph = rasterize(
+                font,
+                size,
+                glyph_id,
+                subpixel,
+                &self.options,
+                &self.coverage_table,
+            );
+            self.insert(key, glyph);
+        }
+
+        let glyph = self.glyphs.get_mut(&key).unwrap();
+        glyph.last_used = self.clock;
+        (
+            origin.0 as i32 + glyph.offset.0,
+            origin.1 as i32 + glyph.offset.1,
+            &glyph.mask,
+        )
+    }
+
+    /// Adds a rasterized glyph, evicting older glyphs if the cap is exceeded
+    fn insert(&mut self, key: GlyphKey, glyph: CachedGlyph) {
+        self.size += glyph.mask.len();
+        self.glyphs.insert(key, glyph);
+        self.evict(Some(key));
+    }
+
+    /// Evicts the least recently used glyphs, other than `keep`, until
+    /// the cache is back to three quarters of its capacity
+    fn evict(&mut self, keep: Option<GlyphKey>) {
+        if self.size <= self.capacity {
+            return;
+        }
+        let mut by_age: Vec<(u64, GlyphKey)> = self
+            .glyphs
+            .iter()
+            .filter(|(key, _)| Some(**key) != keep)
+            .map(|(key, glyph)| (glyph.last_used, *key))
+            .collect();
+        by_age.sort_by_key(|(last_used, _)| *last_used);
+
+        let target = self.capacity / 4 * 3;
+        for (_, key) in by_age {
+            if self.size <= target {
+                break;
+            }
+            if let Some(glyph) = self.glyphs.remove(&key) {
+                self.size -= glyph.mask.len();
+            }
+        }
+    }
+}
+
+impl Default for GlyphCache {
+    fn default() -> Self {
+        GlyphCache::new(DEFAULT_GLYPH_CACHE_CAPACITY)
+    }
+}
+
+/// Rasterizes a glyph whose origin is offset by `subpixel` steps from
+/// the top left corner of a pixel
+fn rasterize(
+    font: &Font,
+    size: f32,
+    glyph_id: u32,
+    subpixel: (u8, u8),
+    options: &TextRenderOptions,
+    coverage_table: &[u8; 256],
+) -> CachedGlyph {
+    let translation = Transform2F::from_translation(Vector2F::new(
+        subpixel.0 as f32 / SUBPIXEL_STEPS,
+        subpixel.1 as f32 / SUBPIXEL_STEPS,
+    ));
+    let hinting = match (options.hinting, options.antialias) {
+        (TextHinting::None, _) => HintingOptions::None,
+        (TextHinting::Vertical, TextAntialias::Subpixel(_)) => HintingOptions::VerticalSubpixel(size),
+        (TextHinting::Vertical, _) => HintingOptions::Vertical(size),
+        (TextHinting::Full, _) => HintingOptions::Full(size),
+    };
+    let (rasterization, format) = match options.antialias {
+        TextAntialias::None => (RasterizationOptions::Bilevel, Format::A8),
+        TextAntialias::Grayscale => (RasterizationOptions::GrayscaleAa, Format::A8),
+        TextAntialias::Subpixel(_) => (RasterizationOptions::SubpixelAa, Format::Rgb24),
+    };
+    let empty = CachedGlyph {
+        offset: (0, 0),
+        mask: GlyphMask::Coverage(Mask {
+            width: 0,
+            height: 0,
+            data: Vec::new(),
+        }),
+        last_used: 0,
+    };
+
+    let bounds = match font.raster_bounds(glyph_id, size, translation, hinting, rasterization) {
+        Ok(bounds) if bounds.width() > 0 && bounds.height() > 0 => bounds,
+        _ => return empty,
+    };
+
+    let mut canvas = Canvas::new(bounds.size(), format);
+    if font
+        .rasterize_glyph(
+            &mut canvas,
+            glyph_id,
+            size,
+            Transform2F::from_translation(-bounds.origin().to_f32()) * translation,
+            hinting,
+            rasterization,
+        )
+        .is_err()
+    {
+        return empty;
+    }
+
+    let row_length = bounds.width() as usize * format.bytes_per_pixel() as usize;
+    let mut data = Vec::with_capacity(row_length * bounds.height() as usize);
+    for row in canvas.pixels.chunks(canvas.stride) {
+        data.extend(row[..row_length].iter().map(|coverage| coverage_table[*coverage as usize]));
+    }
+
+    let mask = match options.antialias {
+        TextAntialias::Subpixel(order) => {
+            if order == SubpixelOrder::Bgr {
+                for pixel in data.chunks_mut(3) {
+                    pixel.swap(0, 2);
+                }
+            }
+            GlyphMask::Subpixel {
+                width: bounds.width(),
+                height: bounds.height(),
+                data,
+            }
+        }
+        _ => GlyphMask::Coverage(Mask {
+            width: bounds.width(),
+            height: bounds.height(),
+            data,
+        }),
+    };
+
+    CachedGlyph {
+        offset: (bounds.origin().x(), bounds.origin().y()),
+        mask,
+        last_used: 0,
+    }
+}
+
+/// Blends `source` through a subpixel `mask`, placed at (`x`, `y`), into
+/// the pixels of `dt` within `clip`, given as its left, top, right and
+/// bottom edges
+///
+/// [`DrawTarget::mask`] only takes a single coverage per pixel, so the
+/// three coverages are blended here instead.
+pub fn blend_subpixel_mask(
+    dt: &mut DrawTarget,
+    x: i32,
+    y: i32,
+    mask: &GlyphMask,
+    source: SolidSource,
+    clip: (i32, i32, i32, i32),
+) {
+    let (width, height, data) = match mask {
+        GlyphMask::Subpixel {
+            width,
+            height,
+            data,
+        } => (*width, *height, data),
+        GlyphMask::Coverage(_) => return,
+    };
+    let dt_width = dt.width();
+    let left = x.max(clip.0).max(0);
+    let top = y.max(clip.1).max(0);
+    let right = (x + width).min(clip.2).min(dt_width);
+    let bottom = (y + height).min(clip.3).min(dt.height());
+
+    let pixels = dt.get_data_mut();
+    let src = [source.r as u32, source.g as u32, source.b as u32];
+    let src_a = source.a as u32;
+    for row in top..bottom {
+        for column in left..right {
+            let index = ((row - y) * width + (column - x)) as usize * 3;
+            let coverage = [
+                data[index] as u32 * src_a / 255,
+                data[index + 1] as u32 * src_a / 255,
+                data[index + 2] as u32 * src_a / 255,
+            ];
+            let pixel = &mut pixels[(row * dt_width + column) as usize];
+            let dst = [(*pixel >> 16) & 0xff, (*pixel >> 8) & 0xff, *pixel & 0xff];
+            let mut blended = [0; 3];
+            for channel in 0..3 {
+                blended[channel] = src[channel] * coverage[channel] / src_a.max(1)
+                    + dst[channel] * (255 - coverage[channel]) / 255;
+            }
+            let alpha_coverage = coverage[0].max(coverage[1]).max(coverage[2]);
+            let alpha = alpha_coverage + (*pixel >> 24) * (255 - alpha_coverage) / 255;
+            *pixel = (alpha << 24) | (blended[0] << 16) | (blended[1] << 8) | blended[2];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(glyph_id: u32) -> GlyphKey {
+        GlyphKey {
+            font_id: 0,
+            size: 16f32.to_bits(),
+            glyph_id,
+            subpixel: (0, 0),
+        }
+    }
+
+    /// Adds a glyph of `len` bytes last drawn at `last_used`
+    fn insert(cache: &mut GlyphCache, glyph_id: u32, len: usize, last_used: u64) {
+        let glyph = CachedGlyph {
+            offset: (0, 0),
+            mask: GlyphMask::Subpixel {
+                width: len as i32 / 3,
+                height: 1,
+                data: vec![0; len],
+            },
+            last_used,
+        };
+        cache.insert(key(glyph_id), glyph);
+    }
+
+    #[test]
+    fn keeps_glyphs_within_capacity() {
+        let mut cache = GlyphCache::new(300);
+        insert(&mut cache, 0, 150, 1);
+        insert(&mut cache, 1, 150, 2);
+        assert_eq!(cache.size(), 300);
+        assert_eq!(cache.glyphs.len(), 2);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_glyphs_to_three_quarters() {
+        let mut cache = GlyphCache::new(400);
+        for glyph_id in 0..4 {
+            insert(&mut cache, glyph_id, 99, 10 - glyph_id as u64);
+        }
+        // Exceeding the cap evicts the oldest glyphs, down to 300 bytes
+        insert(&mut cache, 4, 99, 0);
+        assert_eq!(cache.size(), 297);
+        assert!(!cache.glyphs.contains_key(&key(3)));
+        assert!(!cache.glyphs.contains_key(&key(2)));
+        assert!(cache.glyphs.contains_key(&key(0)));
+        // The glyph just added is kept even though it looks the oldest
+        assert!(cache.glyphs.contains_key(&key(4)));
+    }
+
+    #[test]
+    fn evicts_when_the_capacity_shrinks() {
+        let mut cache = GlyphCache::new(1000);
+        for glyph_id in 0..4 {
+            insert(&mut cache, glyph_id, 90, glyph_id as u64);
+        }
+        cache.set_capacity(200);
+        assert!(cache.size() <= 150);
+        assert!(cache.glyphs.contains_key(&key(3)));
+        assert!(!cache.glyphs.contains_key(&key(0)));
+    }
+
+    #[test]
+    fn changing_options_drops_the_glyphs() {
+        let mut cache = GlyphCache::default();
+        insert(&mut cache, 0, 30, 1);
+        cache.set_options(TextRenderOptions::default()).unwrap();
+        assert_eq!(cache.size(), 30);
+        cache
+            .set_options(TextRenderOptions {
+                gamma: 2.,
+                ..TextRenderOptions::default()
+            })
+            .unwrap();
+        assert_eq!(cache.size(), 0);
+        assert!(cache.glyphs.is_empty());
+    }
+
+    #[test]
+    fn clamps_the_gamma_and_contrast() {
+        let options = |gamma, contrast| TextRenderOptions {
+            gamma,
+            contrast,
+            ..TextRenderOptions::default()
+        };
+        assert_eq!(options(0.01, -1.).clamped(), Ok(options(MIN_GAMMA, 0.)));
+        assert_eq!(options(100., 2.).clamped(), Ok(options(MAX_GAMMA, 1.)));
+        assert_eq!(options(2.2, 0.5).clamped(), Ok(options(2.2, 0.5)));
+        for gamma in [0., -1., f32::NAN, f32::INFINITY].iter() {
+            assert!(options(*gamma, 0.).clamped().is_err());
+        }
+        assert!(options(1., f32::NAN).clamped().is_err());
+
+        // Invalid options leave the cache untouched
+        let mut cache = GlyphCache::default();
+        insert(&mut cache, 0, 30, 1);
+        assert!(cache.set_options(options(0., 0.)).is_err());
+        assert_eq!(cache.options(), TextRenderOptions::default());
+        assert_eq!(cache.size(), 30);
+    }
+
+    #[test]
+    fn default_coverage_is_unchanged() {
+        let table = TextRenderOptions::default().coverage_table();
+        assert!(table.iter().enumerate().all(|(coverage, value)| *value as usize == coverage));
+    }
+}
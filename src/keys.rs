@@ -0,0 +1,178 @@
+//! Mapping of the keyboard of [`minifb`](`crate`) to the key codes of [`hyber`](`crate`)
+
+use crate::EventClient;
+
+use hyber::event;
+use hyber::key_code::KeyCode;
+
+/// State of the modifier keys while a key is pressed or released
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    /// Gets the modifier keys held down on `window`
+    pub fn from_window(window: &minifb::Window) -> Self {
+        Modifiers {
+            shift: window.is_key_down(minifb::Key::LeftShift)
+                || window.is_key_down(minifb::Key::RightShift),
+            control: window.is_key_down(minifb::Key::LeftCtrl)
+                || window.is_key_down(minifb::Key::RightCtrl),
+            alt: window.is_key_down(minifb::Key::LeftAlt)
+                || window.is_key_down(minifb::Key::RightAlt),
+            //TODO: Windows Key
+            logo: window.is_key_down(minifb::Key::LeftCtrl)
+                || window.is_key_down(minifb::Key::RightCtrl),
+        }
+    }
+
+    fn state(&self) -> event::ModifiersState {
+        event::ModifiersState {
+            shift: self.shift,
+            control: self.control,
+            alt: self.alt,
+            logo: self.logo,
+        }
+    }
+}
+
+/// Gets the key code of a [`minifb`](`crate`) key, where C, V and X stand
+/// for copy, paste and cut while `control` is held down
+pub fn map_key(key: minifb::Key, control: bool) -> Option<KeyCode> {
+    Some(match key {
+        minifb::Key::Key0 => KeyCode::Key0,
+        minifb::Key::Key1 => KeyCode::Key1,
+        minifb::Key::Key2 => KeyCode::Key2,
+        minifb::Key::Key3 => KeyCode::Key3,
+        minifb::Key::Key4 => KeyCode::Key4,
+        minifb::Key::Key5 => KeyCode::Key5,
+        minifb::Key::Key6 => KeyCode::Key6,
+        minifb::Key::Key7 => KeyCode::Key7,
+        minifb::Key::Key8 => KeyCode::Key8,
+        minifb::Key::Key9 => KeyCode::Key9,
+        minifb::Key::A => KeyCode::A,
+        minifb::Key::B => KeyCode::B,
+        minifb::Key::C if control => KeyCode::Copy,
+        minifb::Key::C => KeyCode::C,
+        minifb::Key::D => KeyCode::D,
+        minifb::Key::E => KeyCode::E,
+        minifb::Key::F => KeyCode::F,
+        minifb::Key::G => KeyCode::G,
+        minifb::Key::H => KeyCode::H,
+        minifb::Key::I => KeyCode::I,
+        minifb::Key::J => KeyCode::J,
+        minifb::Key::K => KeyCode::K,
+        minifb::Key::L => KeyCode::L,
+        minifb::Key::M => KeyCode::M,
+        minifb::Key::N => KeyCode::N,
+        minifb::Key::O => KeyCode::O,
+        minifb::Key::P => KeyCode::P,
+        minifb::Key::Q => KeyCode::Q,
+        minifb::Key::R => KeyCode::R,
+        minifb::Key::S => KeyCode::S,
+        minifb::Key::T => KeyCode::T,
+        minifb::Key::U => KeyCode::U,
+        minifb::Key::V if control => KeyCode::Paste,
+        minifb::Key::V => KeyCode::V,
+        minifb::Key::W => KeyCode::W,
+        minifb::Key::X if control => KeyCode::Cut,
+        minifb::Key::X => KeyCode::X,
+        minifb::Key::Y => KeyCode::Y,
+        minifb::Key::Z => KeyCode::Z,
+        minifb::Key::F1 => KeyCode::F1,
+        minifb::Key::F2 => KeyCode::F2,
+        minifb::Key::F3 => KeyCode::F3,
+        minifb::Key::F4 => KeyCode::F4,
+        minifb::Key::F5 => KeyCode::F5,
+        minifb::Key::F6 => KeyCode::F6,
+        minifb::Key::F7 => KeyCode::F7,
+        minifb::Key::F8 => KeyCode::F8,
+        minifb::Key::F9 => KeyCode::F9,
+        minifb::Key::F10 => KeyCode::F10,
+        minifb::Key::F11 => KeyCode::F11,
+        minifb::Key::F12 => KeyCode::F12,
+        minifb::Key::F13 => KeyCode::F13,
+        minifb::Key::F14 => KeyCode::F14,
+        minifb::Key::F15 => KeyCode::F15,
+        minifb::Key::Down => KeyCode::Down,
+        minifb::Key::Left => KeyCode::Left,
+        minifb::Key::Right => KeyCode::Right,
+        minifb::Key::Up => KeyCode::Up,
+        minifb::Key::Apostrophe => KeyCode::Apostrophe,
+        // minifb::Key::Backquote => KeyCode::Backquote,
+        minifb::Key::Backslash => KeyCode::Backslash,
+        minifb::Key::Comma => KeyCode::Comma,
+        minifb::Key::Equal => KeyCode::Equals,
+        minifb::Key::LeftBracket => KeyCode::LBracket,
+        minifb::Key::Minus => KeyCode::Minus,
+        minifb::Key::Period => KeyCode::Period,
+        minifb::Key::RightBracket => KeyCode::RBracket,
+        minifb::Key::Semicolon => KeyCode::Semicolon,
+        minifb::Key::Slash => KeyCode::Slash,
+        minifb::Key::Backspace => KeyCode::Backspace,
+        minifb::Key::Delete => KeyCode::Delete,
+        minifb::Key::End => KeyCode::End,
+        minifb::Key::Enter => KeyCode::Enter,
+        minifb::Key::Escape => KeyCode::Escape,
+        minifb::Key::Home => KeyCode::Home,
+        minifb::Key::Insert => KeyCode::Insert,
+        //TODO: Change mapping MENU
+        minifb::Key::Menu => KeyCode::AbntC1,
+        minifb::Key::PageDown => KeyCode::PageDown,
+        minifb::Key::PageUp => KeyCode::PageUp,
+        minifb::Key::Pause => KeyCode::Pause,
+        minifb::Key::Space => KeyCode::Space,
+        minifb::Key::Tab => KeyCode::Tab,
+        minifb::Key::NumLock => KeyCode::Numlock,
+        //TODO: Missing CapsLock code hyber
+        // minifb::Key::CapsLock => KeyCode::CapsLock,
+        minifb::Key::ScrollLock => KeyCode::Scroll,
+        minifb::Key::LeftShift => KeyCode::LShift,
+        minifb::Key::RightShift => KeyCode::RShift,
+        minifb::Key::LeftCtrl => KeyCode::LControl,
+        minifb::Key::RightCtrl => KeyCode::RControl,
+        minifb::Key::NumPad0 => KeyCode::Numpad0,
+        minifb::Key::NumPad1 => KeyCode::Numpad1,
+        minifb::Key::NumPad2 => KeyCode::Numpad2,
+        minifb::Key::NumPad3 => KeyCode::Numpad3,
+        minifb::Key::NumPad4 => KeyCode::Numpad4,
+        minifb::Key::NumPad5 => KeyCode::Numpad5,
+        minifb::Key::NumPad6 => KeyCode::Numpad6,
+        minifb::Key::NumPad7 => KeyCode::Numpad7,
+        minifb::Key::NumPad8 => KeyCode::Numpad8,
+        minifb::Key::NumPad9 => KeyCode::Numpad9,
+        minifb::Key::NumPadDot => KeyCode::Period,
+        minifb::Key::NumPadSlash => KeyCode::NumpadDivide,
+        minifb::Key::NumPadAsterisk => KeyCode::NumpadMultiply,
+        minifb::Key::NumPadMinus => KeyCode::NumpadSubtract,
+        minifb::Key::NumPadPlus => KeyCode::NumpadAdd,
+        minifb::Key::NumPadEnter => KeyCode::NumpadEnter,
+        minifb::Key::LeftAlt => KeyCode::LAlt,
+        minifb::Key::RightAlt => KeyCode::RAlt,
+        _ => return None,
+    })
+}
+
+/// Gets the events of the keys pressed, or released, on a frame
+pub fn key_events(keys: &[minifb::Key], pressed: bool, modifiers: Modifiers) -> Vec<EventClient> {
+    keys.iter()
+        .filter_map(|key| map_key(*key, modifiers.control))
+        .map(|key_code| {
+            if pressed {
+                EventClient::KeyPressed {
+                    key_code,
+                    modifiers: modifiers.state(),
+                }
+            } else {
+                EventClient::KeyReleased {
+                    key_code,
+                    modifiers: modifiers.state(),
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+//! Retained offscreen layers
+//!
+//! The instructions of the widget keys marked as cached are rasterized once
+//! into a layer of their own, which is blitted on the following frames for
+//! as long as the hash of the instructions stays the same.
+
+use crate::geometry::Rect;
+
+use crate::text::TextSpan;
+
+use hyber::renderer::RenderInstruction;
+
+use raqote::DrawTarget;
+
+use std::collections::{HashMap, HashSet};
+
+/// Default memory budget of a [`LayerCache`], in bytes
+pub const DEFAULT_LAYER_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Something drawn on a frame
+#[derive(Clone, Copy)]
+pub enum DrawItem<'a> {
+    /// A render instruction, drawn as is
+    Instruction(&'a RenderInstruction),
+    /// A [`RenderInstruction::DrawText`] whose string is replaced by the
+    /// rich text of its widget key, in logical units
+    RichText {
+        instruction: &'a RenderInstruction,
+        spans: &'a [TextSpan],
+    },
+    /// The premultiplied ARGB pixels of a layer, with their top left
+    /// corner at (`left`, `top`)
+    Layer {
+        left: i32,
+        top: i32,
+        width: i32,
+        height: i32,
+        pixels: &'a [u32],
+    },
+}
+
+/// Instructions of a widget key rasterized offscreen
+pub struct Layer {
+    /// Hash of the instructions drawn on the layer
+    pub hash: u64,
+    /// Where the layer goes on the draw target
+    pub bounds: Rect,
+    /// The pixels of the layer
+    pub dt: DrawTarget,
+    /// Value of the cache's clock when the layer was last used
+    last_used: u64,
+}
+
+impl Layer {
+    pub fn new(hash: u64, bounds: Rect, dt: DrawTarget) -> Self {
+        Layer {
+            hash,
+            bounds,
+            dt,
+            last_used: 0,
+        }
+    }
+
+    /// Memory, in bytes, used by the layer
+    fn size(&self) -> usize {
+        self.dt.get_data().len() * 4
+    }
+}
+
+/// Layers of the widget keys marked as cached, limited to a memory budget
+///
+/// When the budget is exceeded, the least recently used layers are evicted.
+#[derive(Default)]
+pub struct LayerCache {
+    /// Widget keys whose instructions are drawn through layers
+    cached_keys: HashSet<usize>,
+    layers: HashMap<usize, Layer>,
+    /// Maximum memory, in bytes, used by the layers
+    budget: usize,
+    /// Memory, in bytes, currently used by the layers
+    size: usize,
+    /// Counter increased on every frame
+    clock: u64,
+}
+
+impl LayerCache {
+    pub fn new(budget: usize) -> Self {
+        LayerCache {
+            budget,
+            ..LayerCache::default()
+        }
+    }
+
+    /// Changes the memory budget, evicting layers if needed
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    /// Memory, in bytes, currently used by the layers
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the instructions of `key` are drawn through a layer
+    pub fn is_cached(&self, key: usize) -> bool {
+        self.cached_keys.contains(&key)
+    }
+
+    /// Marks whether the instructions of `key` are drawn through a layer
+    pub fn set_cached(&mut self, key: usize, cached: bool) {
+        if cached {
+            self.cached_keys.insert(key);
+        } else {
+            self.cached_keys.remove(&key);
+            self.invalidate(key);
+        }
+    }
+
+    /// Drops the layer of `key`, so that it is rasterized again
+    pub fn invalidate(&mut self, key: usize) {
+        if let Some(layer) = self.layers.remove(&key) {
+            self.size -= layer.size();
+        }
+    }
+
+    /// Drops every layer
+    pub fn invalidate_all(&mut self) {
+        self.layers.clear();
+        self.size = 0;
+    }
+
+    /// Starts a new frame, for the layers' ages
+    pub fn tick(&mut self) {
+        self.clock += 1;
+    }
+
+    /// Whether `key` has a layer holding instructions of hash `hash`,
+    /// marking it as used on this frame
+    pub fn is_valid(&mut self, key: usize, hash: u64) -> bool {
+        match self.layers.get_mut(&key) {
+            Some(layer) if layer.hash == hash => {
+                layer.last_used = self.clock;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets the layer of `key`, if it has one
+    pub fn get(&self, key: usize) -> Option<&Layer> {
+        self.layers.get(&key)
+    }
+
+    /// Stores the layer of `key`, unless it alone exceeds the budget
+    pub fn insert(&mut self, key: usize, mut layer: Layer) {
+        self.invalidate(key);
+        if layer.size() > self.budget {
+            return;
+        }
+        layer.last_used = self.clock;
+        self.size += layer.size();
+        self.layers.insert(key, layer);
+        self.evict();
+    }
+
+    /// Evicts the least recently used layers until the budget is met
+    fn evict(&mut self) {
+        while self.size > self.budget {
+            let oldest = self
+                .layers
+                .iter()
+                .min_by_key(|(_, layer)| layer.last_used)
+                .map(|(key, _)| *key);
+            match oldest {
+                Some(key) => self.invalidate(key),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Layer of 10 by 10 pixels, using 400 bytes
+    fn layer(hash: u64) -> Layer {
+        Layer::new(hash, Rect::new(0, 0, 10, 10), DrawTarget::new(10, 10))
+    }
+
+    #[test]
+    fn reuses_layers_while_the_hash_is_the_same() {
+        let mut cache = LayerCache::new(1000);
+        cache.insert(0, layer(1));
+        assert!(cache.is_valid(0, 1));
+        assert!(!cache.is_valid(0, 2));
+        assert!(!cache.is_valid(1, 1));
+        assert_eq!(cache.size(), 400);
+
+        // Replacing a layer does not count it twice
+        cache.insert(0, layer(2));
+        assert!(cache.is_valid(0, 2));
+        assert_eq!(cache.size(), 400);
+    }
+
+    #[test]
+    fn drops_invalidated_layers() {
+        let mut cache = LayerCache::new(1000);
+        cache.set_cached(0, true);
+        cache.insert(0, layer(1));
+        cache.insert(1, layer(1));
+        cache.invalidate(1);
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.size(), 400);
+
+        // Keys no longer cached lose their layer
+        cache.set_cached(0, false);
+        assert!(!cache.is_cached(0));
+        assert!(cache.get(0).is_none());
+        assert_eq!(cache.size(), 0);
+
+        cache.insert(0, layer(1));
+        cache.insert(1, layer(1));
+        cache.invalidate_all();
+        assert!(cache.get(0).is_none() && cache.get(1).is_none());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_layers() {
+        let mut cache = LayerCache::new(1200);
+        for key in 0..3 {
+            cache.insert(key, layer(1));
+            cache.tick();
+        }
+        // Using the oldest layer makes the second one the oldest
+        assert!(cache.is_valid(0, 1));
+        cache.insert(3, layer(1));
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(0).is_some() && cache.get(2).is_some() && cache.get(3).is_some());
+        assert_eq!(cache.size(), 1200);
+
+        cache.set_budget(500);
+        assert_eq!(cache.size(), 400);
+        assert!(cache.get(3).is_some());
+    }
+
+    #[test]
+    fn skips_layers_over_the_budget() {
+        let mut cache = LayerCache::new(300);
+        cache.insert(0, layer(1));
+        assert!(cache.get(0).is_none());
+        assert_eq!(cache.size(), 0);
+    }
+}
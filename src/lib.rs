@@ -1,22 +1,57 @@
 //! Mapping between the minifb and raqote with [`hyber`](`crate`)
 
 use hyber::{
-    display::Display, display::DisplayDescritor, event, key_code, renderer::DrawImageOptions,
+    display::Display, display::DisplayDescritor, event, renderer::DrawImageOptions,
     renderer::RenderInstruction, renderer::RenderInstructionCollection, renderer::Renderer,
     util::Color, util::Queue, util::Vector2D,
 };
 
 use minifb;
 use raqote::{
-    DrawOptions, DrawTarget, LineCap, LineJoin, PathBuilder, SolidSource, Source, StrokeStyle,
+    BlendMode, DrawOptions, DrawTarget, IntPoint, IntRect, LineCap, LineJoin, PathBuilder,
+    SolidSource, Source, StrokeStyle, Transform,
 };
 // use std::os::raw; for window handle
 
-use font_kit::family_name::FamilyName;
-use font_kit::properties::Properties;
-use font_kit::source::SystemSource;
+use image::{open, GenericImageView, RgbaImage};
 
-use image::open;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub mod color_glyph;
+pub mod cursor;
+pub mod damage;
+pub mod geometry;
+pub mod glyph_cache;
+pub mod keys;
+pub mod layers;
+pub mod menu;
+pub mod pacing;
+pub mod pixels;
+pub mod text;
+pub mod window;
+pub mod window_set;
+#[cfg(feature = "parallel")]
+pub mod tiles;
+
+use color_glyph::{ColorGlyphCache, ColorLayer};
+use cursor::{Cursor, CursorControl};
+use damage::{Damage, DamageTracker};
+use geometry::{hash_instructions, instruction_bounds, scale_instruction, scale_vector, Rect};
+use keys::{key_events, Modifiers};
+use layers::{DrawItem, Layer, LayerCache, DEFAULT_LAYER_BUDGET};
+use menu::MenuTree;
+use pacing::{FramePacer, FrameRate, FrameStats, InvalidFrameRate};
+use pixels::{argb_to_image, image_to_argb, unpremultiply_argb};
+use glyph_cache::{
+    blend_subpixel_mask, GlyphCache, GlyphMask, InvalidTextRenderOptions, TextRenderOptions,
+};
+use text::{FontCache, TextLayout, TextSpan, TextStyle};
+use window::{CloseControl, DisplayBuilder, DisplayError, FitMode, PresentAlpha, WindowEvent};
+#[cfg(feature = "parallel")]
+use tiles::TiledRendering;
 
 /// Type of events that could occur by the client
 pub enum EventClient {
@@ -41,7 +76,7 @@ pub enum EventClient {
     MouseEntered,
     /// Move left the window display
     MouseLeft,
-    /// Scroll of the mouse
+    /// Scroll of the mouse, in logical units
     Scroll {
         x: f32,
         y: f32,
@@ -73,7 +108,7 @@ pub enum EventClient {
 /// and cursor's position
 #[derive(Default)]
 pub struct MouseState {
-    /// The current position of the mouse
+    /// The current position of the mouse on the window, in physical pixels
     pub mouse_pos: (f32, f32),
     /// Wheter the left mouse button is pressed
     pub button_left: bool,
@@ -87,8 +122,51 @@ pub struct MouseState {
 
 /// State of the window
 pub struct WindowState {
-    /// The current window's size (width and height)
+    /// The current window's size (width and height), in physical pixels
     pub window_size: (usize, usize),
+    /// Whether the window has a border
+    pub border: bool,
+    /// Whether the window can be resized by the user
+    pub resizable: bool,
+    /// Whether the window can be minimized, which is the case of the
+    /// windows with a title bar
+    pub minimizable: bool,
+    /// Whether the window stays on top of the other windows
+    pub topmost: bool,
+    /// Whether closing the window was requested and not cancelled yet
+    pub close_requested: bool,
+    /// Whether the window has the keyboard focus
+    pub focused: bool,
+    /// The current position of the window's top left corner on the screen,
+    /// in physical pixels
+    pub position: (isize, isize),
+    /// Whether the window is minimized
+    ///
+    /// [`minifb`](`crate`) doesn't report it, so it is guessed from the
+    /// window having no area or, on Windows only, from the position given
+    /// to minimized windows. Other platforms may not detect it at all.
+    pub minimized: bool,
+}
+
+/// Position given by Windows, and no other platform, to minimized windows
+const MINIMIZED_POSITION: isize = -32000;
+
+/// Time [`minifb`](`crate`) waits between updates of a window by default
+pub const DEFAULT_UPDATE_RATE: Duration = Duration::from_millis(4);
+
+/// Environment variable overriding the scale factor of the displays
+/// created with [`Display::new`]
+pub const SCALE_FACTOR_VAR: &str = "HYBER_SCALE_FACTOR";
+
+/// Gets the scale factor set in [`SCALE_FACTOR_VAR`], if it holds a
+/// positive number
+pub(crate) fn scale_factor_from_env() -> Option<f64> {
+    std::env::var(SCALE_FACTOR_VAR)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|scale: &f64| *scale > 0. && scale.is_finite())
 }
 
 /// Configuration of the display window and mouse according
@@ -100,38 +178,351 @@ pub struct DisplayMinifb {
     pub mouse_state: MouseState,
     /// The status of the display window
     pub window_state: WindowState,
+    /// Whether the last call to [`Renderer::detect_display_events`] found
+    /// any input
+    pub input_detected: bool,
+    /// Physical pixels per logical unit
+    ///
+    /// The window and the rendering use physical pixels, while the size
+    /// reported by [`Display::get_size`] and the mouse events are in
+    /// logical units.
+    pub scale_factor: f64,
+    /// Whether the window lost its pixels, such as after being created
+    /// again, so that the next frame must be presented even if nothing
+    /// changed
+    pub needs_redraw: bool,
+    /// How the alpha of the pixels is given to the window, when it is
+    /// transparent
+    pub present_alpha: PresentAlpha,
+    /// The cursor shown over the window, which can be shared with the
+    /// messages changing it
+    pub cursor: Rc<RefCell<CursorControl>>,
+    /// The cursor last given to the window
+    applied_cursor: Cursor,
+    /// Close requests and their answer, which can be shared with the
+    /// messages vetoing them
+    pub close_control: Rc<RefCell<CloseControl>>,
+    /// Events of the window that have no [`hyber`](`crate`) event, which
+    /// can be shared with the messages handling them
+    ///
+    /// They are queued by [`Renderer::detect_display_events`] and stay
+    /// queued until the application takes them.
+    pub window_events: Rc<RefCell<VecDeque<WindowEvent>>>,
+    /// Key requesting to close the window, if any, which is Escape unless
+    /// the window was built with another
+    pub quit_key: Option<minifb::Key>,
+    /// Whether the window was closed, after a close request went through
+    closed: bool,
+    /// Time [`minifb`](`crate`) waits between updates of the window, when
+    /// the frames are not paced by the renderer
+    update_rate: Option<Duration>,
+    /// Whether the renderer paces the frames, instead of [`minifb`](`crate`)
+    paced: bool,
+    /// Menus of the window's menu bar
+    menu_bar: Vec<MenuTree>,
+    /// Handles of the menus added to the window
+    menu_handles: Vec<minifb::MenuHandle>,
+    /// The window's title
+    title: String,
+    /// Options the window was created with
+    options: minifb::WindowOptions,
+    /// The window's background color, if it was set
+    background_color: Option<(usize, usize, usize)>,
+    /// Smallest size, in logical units, the contents are laid out at
+    min_size: Option<(usize, usize)>,
+    /// Largest size, in logical units, the contents are laid out at
+    max_size: Option<(usize, usize)>,
+    /// Size, in logical units, the contents are always laid out at, and
+    /// how they fit in the window
+    fixed_resolution: Option<(usize, usize, FitMode)>,
+    /// Error of the last window change made through [`Display`] or by a
+    /// vetoed close request, which can't return it
+    last_error: Option<DisplayError>,
+}
+
+impl DisplayMinifb {
+    /// Starts building a window of `width` by `height` logical units
+    pub fn builder(title: &str, width: usize, height: usize) -> DisplayBuilder {
+        DisplayBuilder::new(title, width, height)
+    }
+
+    /// Creates a display of `width` by `height` logical units, drawn with
+    /// `scale_factor` physical pixels per unit
+    pub fn with_scale_factor(
+        title: &str,
+        width: usize,
+        height: usize,
+        display_descriptor: DisplayDescritor,
+        scale_factor: f64,
+    ) -> Self {
+        match DisplayBuilder::from_descriptor(title, width, height, display_descriptor)
+            .scale_factor(scale_factor)
+            .build()
+        {
+            Ok(display) => display,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Size, in physical pixels, of the area the contents are drawn on,
+    /// which is the window's size kept within its minimum and maximum sizes,
+    /// or the fixed resolution
+    pub fn surface_size(&self) -> (usize, usize) {
+        let physical = |size: usize| (size as f64 * self.scale_factor).round() as usize;
+        if let Some((width, height, _)) = self.fixed_resolution {
+            return (physical(width), physical(height));
+        }
+        let (mut width, mut height) = self.display.get_size();
+        if let Some((min_width, min_height)) = self.min_size {
+            width = width.max(physical(min_width));
+            height = height.max(physical(min_height));
+        }
+        if let Some((max_width, max_height)) = self.max_size {
+            width = width.min(physical(max_width));
+            height = height.min(physical(max_height));
+        }
+        (width, height)
+    }
+
+    /// Size, in logical units, the contents are always laid out at, and how
+    /// they fit in the window, if the resolution is fixed
+    pub fn fixed_resolution(&self) -> Option<(usize, usize, FitMode)> {
+        self.fixed_resolution
+    }
+
+    /// Converts a point of the window, in physical pixels, to the point of
+    /// the surface drawn there, undoing how a fixed resolution is fitted
+    ///
+    /// Nothing is drawn on a window without area, such as a minimized one,
+    /// which gives `None`.
+    pub fn to_surface(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (window_width, window_height) = self.display.get_size();
+        let window = (window_width as f32, window_height as f32);
+        match self.fixed_resolution {
+            Some((_, _, fit)) => {
+                let (width, height) = self.surface_size();
+                fit.to_surface(window, (width as f32, height as f32), (x, y))
+            }
+            None if window.0 > 0. && window.1 > 0. => Some((x, y)),
+            None => None,
+        }
+    }
+
+    /// Size, in physical pixels, of the area the mouse is over the contents
+    /// in, as given by [`DisplayMinifb::to_surface`]
+    fn mouse_area(&self) -> (f32, f32) {
+        let (width, height) = if self.fixed_resolution.is_some() {
+            self.surface_size()
+        } else {
+            self.display.get_size()
+        };
+        (width as f32, height as f32)
+    }
+
+    /// Creates the window again with the options of [`DisplayMinifb::window_state`],
+    /// keeping its title, position and size
+    ///
+    /// [`minifb`](`crate`) can't change these options on a live window. The
+    /// new window has no pixels until the next frame is presented.
+    fn recreate(&mut self) -> Result<(), minifb::Error> {
+        let (width, height) = self.display.get_size();
+        let (x, y) = self.display.get_position();
+        let options = minifb::WindowOptions {
+            borderless: !self.window_state.border,
+            // Borderless windows can't have a title bar
+            title: self.options.title && self.window_state.border,
+            resize: self.window_state.resizable,
+            topmost: self.window_state.topmost,
+            scale: self.options.scale,
+            scale_mode: self.options.scale_mode,
+            transparency: self.options.transparency,
+        };
+        // The old window is kept until everything of the new one is ready
+        let menus = self
+            .menu_bar
+            .iter()
+            .map(MenuTree::build)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.display = minifb::Window::new(&self.title, width, height, options)?;
+
+        self.display.set_position(x, y);
+        self.apply_update_rate();
+        if let Some((red, green, blue)) = self.background_color {
+            self.display.set_background_color(red, green, blue);
+        }
+        self.display.set_cursor_style(self.applied_cursor.style());
+        self.menu_handles = menus.iter().map(|menu| self.display.add_menu(menu)).collect();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    /// Shows or hides the window's border, creating the window again
+    ///
+    /// If the new window can't be created, the old one is kept.
+    pub fn set_border(&mut self, border: bool) -> Result<(), minifb::Error> {
+        if self.window_state.border != border {
+            self.window_state.border = border;
+            if let Err(e) = self.recreate() {
+                self.window_state.border = !border;
+                return Err(e);
+            }
+            self.window_state.minimizable = self.options.title && border;
+        }
+        Ok(())
+    }
+
+    /// Lets the user resize the window or not, creating the window again
+    ///
+    /// If the new window can't be created, the old one is kept.
+    pub fn set_resizable(&mut self, resizable: bool) -> Result<(), minifb::Error> {
+        if self.window_state.resizable != resizable {
+            self.window_state.resizable = resizable;
+            if let Err(e) = self.recreate() {
+                self.window_state.resizable = !resizable;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows or hides the window's minimize button
+    ///
+    /// [`minifb`](`crate`) only shows the minimize button along the title
+    /// bar and the close button, so this fails unless the window already is
+    /// as asked, and [`DisplayMinifb::set_border`] should be used instead.
+    pub fn set_minimizable(&mut self, minimizable: bool) -> Result<(), DisplayError> {
+        if self.window_state.minimizable != minimizable {
+            return Err(DisplayError::MinimizableUnsupported);
+        }
+        Ok(())
+    }
+
+    /// Takes the error of the last window change made through [`Display`],
+    /// such as by [`Display::border`], or of the last vetoed close request
+    /// that couldn't bring the window back, if any
+    pub fn take_error(&mut self) -> Option<DisplayError> {
+        self.last_error.take()
+    }
+
+    /// Vetoes the pending close request, such as to ask about unsaved
+    /// changes first, bringing the window back if the user closed it
+    ///
+    /// If the window can't be brought back, the close goes through. Messages,
+    /// which can't borrow the display, veto through [`DisplayMinifb::close_control`].
+    pub fn cancel_close(&mut self) -> Result<(), minifb::Error> {
+        if self.window_state.close_requested {
+            if !self.display.is_open() {
+                self.recreate()?;
+            }
+            self.window_state.close_requested = false;
+            self.close_control.borrow_mut().set_requested(false);
+        }
+        Ok(())
+    }
+
+    /// Closes the window right away
+    pub fn close(&mut self) {
+        self.window_state.close_requested = false;
+        self.close_control.borrow_mut().set_requested(false);
+        self.closed = true;
+    }
+
+    /// Replaces the menus of the window's menu bar
+    pub fn set_menu_bar(&mut self, menus: Vec<MenuTree>) -> Result<(), minifb::Error> {
+        let built = menus
+            .iter()
+            .map(MenuTree::build)
+            .collect::<Result<Vec<_>, _>>()?;
+        for handle in self.menu_handles.drain(..) {
+            self.display.remove_menu(handle);
+        }
+        for menu in built.iter() {
+            self.menu_handles.push(self.display.add_menu(menu));
+        }
+        self.menu_bar = menus;
+        Ok(())
+    }
+
+    /// Menus of the window's menu bar
+    pub fn menu_bar(&self) -> &[MenuTree] {
+        &self.menu_bar
+    }
+
+    /// Shows `cursor` over the whole window, instead of the cursors of
+    /// the map of [`DisplayMinifb::cursor`]
+    pub fn set_cursor(&mut self, cursor: Cursor) {
+        self.cursor.borrow_mut().set(cursor);
+    }
+
+    /// Goes back to the cursors of the map of [`DisplayMinifb::cursor`]
+    pub fn reset_cursor(&mut self) {
+        self.cursor.borrow_mut().reset();
+    }
+
+    /// Gives the window the cursor for the current mouse position, if it
+    /// changed
+    fn apply_cursor(&mut self) {
+        let (x, y) = self.mouse_state.mouse_pos;
+        let (x, y) = match self.to_surface(x, y) {
+            Some(point) => point,
+            None => return,
+        };
+        let cursor = self
+            .cursor
+            .borrow()
+            .cursor_at(self.to_logical(x), self.to_logical(y));
+        if cursor != self.applied_cursor {
+            self.display.set_cursor_style(cursor.style());
+            self.applied_cursor = cursor;
+        }
+    }
+
+    /// Changes the time [`minifb`](`crate`) waits between updates of the
+    /// window, while the renderer doesn't pace the frames itself
+    pub fn set_update_rate(&mut self, update_rate: Option<Duration>) {
+        self.update_rate = update_rate;
+        if !self.paced {
+            self.display.limit_update_rate(update_rate);
+        }
+    }
+
+    /// Lets the renderer pace the frames, or gives the update rate back
+    /// to [`minifb`](`crate`)
+    fn set_paced(&mut self, paced: bool) {
+        if self.paced != paced {
+            self.paced = paced;
+            self.apply_update_rate();
+        }
+    }
+
+    fn apply_update_rate(&mut self) {
+        let update_rate = if self.paced { None } else { self.update_rate };
+        self.display.limit_update_rate(update_rate);
+    }
+
+    /// Whether the window is see-through where the drawn pixels are translucent
+    pub fn is_transparent(&self) -> bool {
+        self.options.transparency
+    }
+
+    /// Converts a length in physical pixels to logical units
+    pub fn to_logical(&self, value: f32) -> f32 {
+        value / self.scale_factor as f32
+    }
 }
 
 impl Display for DisplayMinifb {
     type Buffer = Vec<u32>;
 
     fn new(title: &str, width: usize, height: usize, display_descriptor: DisplayDescritor) -> Self {
-        match minifb::Window::new(
-            title,
-            width,
-            height,
-            minifb::WindowOptions {
-                borderless: display_descriptor.border,
-                title: display_descriptor.titled,
-                resize: display_descriptor.resizable,
-                topmost: display_descriptor.topmost,
-                scale: minifb::Scale::X1,
-                scale_mode: minifb::ScaleMode::UpperLeft,
-                transparency: false,
-            },
-        ) {
-            Ok(display) => DisplayMinifb {
-                display: display,
-                mouse_state: MouseState::default(),
-                window_state: WindowState {
-                    window_size: (width, height),
-                },
-            },
-            Err(_) => panic!(),
+        match DisplayBuilder::from_descriptor(title, width, height, display_descriptor).build() {
+            Ok(display) => display,
+            Err(e) => panic!("{}", e),
         }
     }
 
     fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
         self.display.set_title(title);
     }
     fn update(&mut self) {
@@ -146,34 +537,54 @@ impl Display for DisplayMinifb {
     }
 
     fn is_open(&self) -> bool {
-        self.display.is_open()
+        // A window closed by the user stays open while its close request
+        // can still be cancelled
+        !self.closed && (self.display.is_open() || self.window_state.close_requested)
     }
 
     fn set_position(&mut self, x: usize, y: usize) {
         self.display.set_position(x as isize, y as isize);
     }
+    /// Shows or hides the window's border, as
+    /// [`DisplayMinifb::set_border`], keeping the error for
+    /// [`DisplayMinifb::take_error`]
     fn border(&mut self, border: bool) {
-        unimplemented!();
+        if let Err(e) = self.set_border(border) {
+            self.last_error = Some(DisplayError::Window(e));
+        }
     }
 
+    /// Lets the user resize the window or not, as
+    /// [`DisplayMinifb::set_resizable`], keeping the error for
+    /// [`DisplayMinifb::take_error`]
     fn resizable(&mut self, resizable: bool) {
-        unimplemented!();
+        if let Err(e) = self.set_resizable(resizable) {
+            self.last_error = Some(DisplayError::Window(e));
+        }
     }
 
     fn topmost(&mut self, topmost: bool) {
+        self.window_state.topmost = topmost;
         self.display.topmost(topmost);
     }
 
+    /// Shows or hides the minimize button, as
+    /// [`DisplayMinifb::set_minimizable`], which is unsupported and keeps
+    /// an error for [`DisplayMinifb::take_error`] unless the window already
+    /// is as asked
     fn minimizable(&mut self, minimizable: bool) {
-        unimplemented!();
+        if let Err(e) = self.set_minimizable(minimizable) {
+            self.last_error = Some(e);
+        }
     }
 
     fn set_background_color(&mut self, red: usize, green: usize, blue: usize) {
+        self.background_color = Some((red, green, blue));
         self.display.set_background_color(red, green, blue);
     }
     fn get_size(&self) -> Vector2D {
-        let (x, y) = self.display.get_size();
-        Vector2D::from_tuple((x as f64, y as f64))
+        let (x, y) = self.surface_size();
+        Vector2D::from_tuple((x as f64 / self.scale_factor, y as f64 / self.scale_factor))
     }
 
     fn is_active(&mut self) -> bool {
@@ -181,23 +592,471 @@ impl Display for DisplayMinifb {
     }
 }
 
+/// Extra room, as a fraction of the pixels needed, allocated when the draw
+/// target of [`Raqote`] grows
+const RESIZE_HEADROOM: f64 = 0.25;
+
+/// Number of render instructions drawn and culled on a frame
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CullStats {
+    /// Instructions painting within the area being drawn
+    pub drawn: usize,
+    /// Instructions skipped for lying outside the area being drawn
+    /// or their clip
+    pub culled: usize,
+}
+
 /// `DrawTarget` of [`raqote`](`crate`) to use as a 
 /// reference for the primitives trait
 pub struct Raqote {
     pub dt: DrawTarget,
+    /// Fonts already loaded by the text primitives
+    fonts: FontCache,
+    /// Glyphs already rasterized by the text primitives
+    glyphs: GlyphCache,
+    /// Color tables and bitmaps of the fonts used by the text primitives
+    color_glyphs: ColorGlyphCache,
+    /// Instructions drawn on the previous frame, to find what changed
+    damage: DamageTracker,
+    /// Area being painted again, while a frame is only partially redrawn
+    damaged_region: Option<Rect>,
+    /// Premultiplied ARGB color of the last clear, used for the pixels
+    /// uncovered when the draw target grows
+    background: u32,
+    /// Clip pushed by the last instruction, as its point and size, which
+    /// is kept for the next instructions sharing it
+    current_clip: Option<[f64; 4]>,
+    /// Offscreen layers of the widget keys marked as cached
+    layers: LayerCache,
+    /// Spans drawn instead of the strings of the text instructions of
+    /// each widget key, in logical units
+    rich_texts: HashMap<usize, Vec<TextSpan>>,
+    /// Options of the multithreaded rasterization, when it is enabled
+    #[cfg(feature = "parallel")]
+    tiled: Option<TiledRendering>,
+    /// Timing of the frames drawn by [`Renderer::draw_collection`]
+    pacer: FramePacer,
+    /// Instructions drawn and culled on the last frame
+    cull_stats: CullStats,
+    /// Physical pixels per logical unit of the instructions given to
+    /// [`Raqote::render_collection`]
+    scale: f64,
+    /// Pixels of the draw target with straight alpha, presented on
+    /// transparent windows
+    present_buffer: Vec<u32>,
 }
 
 impl Raqote {
     pub fn new(width: i32, height: i32) -> Self {
         Self {
             dt: DrawTarget::new(width, height),
+            fonts: FontCache::new(),
+            glyphs: GlyphCache::default(),
+            color_glyphs: ColorGlyphCache::new(),
+            damage: DamageTracker::new(),
+            damaged_region: None,
+            background: 0,
+            current_clip: None,
+            layers: LayerCache::new(DEFAULT_LAYER_BUDGET),
+            rich_texts: HashMap::new(),
+            #[cfg(feature = "parallel")]
+            tiled: None,
+            pacer: FramePacer::default(),
+            cull_stats: CullStats::default(),
+            scale: 1.,
+            present_buffer: Vec::new(),
+        }
+    }
+
+    /// Physical pixels per logical unit of the collections drawn
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Changes the physical pixels per logical unit of the collections
+    /// drawn, such as for a HiDPI display
+    ///
+    /// Widgets keep their coordinates in logical units, while the draw
+    /// target holds physical pixels.
+    pub fn set_scale(&mut self, scale: f64) {
+        if self.scale != scale {
+            self.scale = scale;
+            self.damage.set_scale(scale);
+            self.layers.invalidate_all();
+        }
+    }
+
+    /// Number of render instructions drawn and culled on the last frame
+    /// drawn by [`Renderer::draw_collection`]
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+
+    /// How often [`Renderer::draw_collection`] draws frames
+    pub fn frame_rate(&self) -> FrameRate {
+        self.pacer.rate()
+    }
+
+    /// Changes how often [`Renderer::draw_collection`] draws frames, with
+    /// the rates clamped as by [`FrameRate::clamped`]
+    pub fn set_frame_rate(&mut self, rate: FrameRate) -> Result<(), InvalidFrameRate> {
+        self.pacer.set_rate(rate)
+    }
+
+    /// Timing statistics of the frames drawn so far
+    pub fn frame_stats(&self) -> FrameStats {
+        self.pacer.stats()
+    }
+
+    /// Clears the timing statistics of the frames drawn so far
+    pub fn reset_frame_stats(&mut self) {
+        self.pacer.reset_stats();
+    }
+
+    /// Enables the multithreaded tiled rasterization with the given options,
+    /// or goes back to drawing on a single thread with `None`
+    #[cfg(feature = "parallel")]
+    pub fn set_tiled_rendering(&mut self, tiled: Option<TiledRendering>) {
+        self.tiled = tiled;
+    }
+
+    /// How the text primitives rasterize glyphs
+    pub fn text_render_options(&self) -> TextRenderOptions {
+        self.glyphs.options()
+    }
+
+    /// Changes the hinting, antialiasing, gamma and contrast used by
+    /// the text primitives, clamped as by [`TextRenderOptions::clamped`]
+    pub fn set_text_render_options(
+        &mut self,
+        options: TextRenderOptions,
+    ) -> Result<(), InvalidTextRenderOptions> {
+        let previous = self.glyphs.options();
+        self.glyphs.set_options(options)?;
+        if self.glyphs.options() != previous {
+            // Text already drawn, on the screen or in layers, used the old options
+            self.damage.invalidate();
+            self.layers.invalidate_all();
         }
+        Ok(())
+    }
+
+    /// Changes the memory cap, in bytes, of the rasterized glyphs cache
+    pub fn set_glyph_cache_capacity(&mut self, capacity: usize) {
+        self.glyphs.set_capacity(capacity);
     }
 
+    /// Fills the draw target with `color`, replacing its pixels, so that a
+    /// color with an alpha of zero clears it to fully transparent
     fn clear(&mut self, color: &Color) {
-        self.dt.clear(SolidSource::from_unpremultiplied_argb(
-            color.a, color.r, color.g, color.b,
+        self.reset_clip();
+        let source = SolidSource::from_unpremultiplied_argb(color.a, color.r, color.g, color.b);
+        self.background = ((source.a as u32) << 24)
+            | ((source.r as u32) << 16)
+            | ((source.g as u32) << 8)
+            | (source.b as u32);
+        match self.damaged_region {
+            // Clearing ignores the clip, so only the damaged pixels are filled
+            Some(region) => {
+                let mut options = DrawOptions::new();
+                options.blend_mode = BlendMode::Src;
+                self.dt.fill_rect(
+                    region.left as f32,
+                    region.top as f32,
+                    region.width() as f32,
+                    region.height() as f32,
+                    &Source::Solid(source),
+                    &options,
+                );
+            }
+            None => self.dt.clear(source),
+        }
+    }
+
+    /// Resizes the draw target, keeping its content anchored to the top
+    /// left corner and filling the uncovered pixels with the last clear color
+    ///
+    /// The pixels stay in the same buffer, which is grown with some headroom,
+    /// so that a live resize of the window doesn't reallocate every frame.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.reset_clip();
+        let old_width = self.dt.width().max(0) as usize;
+        let old_height = self.dt.height().max(0) as usize;
+        let new_width = width.max(0) as usize;
+        let new_height = height.max(0) as usize;
+        let needed = new_width * new_height;
+        let background = self.background;
+
+        let mut pixels = std::mem::replace(&mut self.dt, DrawTarget::new(0, 0)).into_vec();
+        if pixels.capacity() < needed {
+            let headroom = (needed as f64 * RESIZE_HEADROOM) as usize;
+            pixels.reserve_exact(needed + headroom - pixels.len());
+        }
+
+        // Moves the rows to their new stride, from the last row when the
+        // rows get longer so that no row is overwritten before it is moved
+        let rows = old_height.min(new_height);
+        let columns = old_width.min(new_width);
+        if new_width > old_width {
+            pixels.resize(pixels.len().max(needed), background);
+            for row in (0..rows).rev() {
+                let start = row * old_width;
+                pixels.copy_within(start..start + columns, row * new_width);
+                for pixel in pixels[row * new_width + columns..(row + 1) * new_width].iter_mut() {
+                    *pixel = background;
+                }
+            }
+        } else {
+            for row in 0..rows {
+                let start = row * old_width;
+                pixels.copy_within(start..start + columns, row * new_width);
+            }
+        }
+        pixels.resize(needed, background);
+        for pixel in pixels[rows * new_width..].iter_mut() {
+            *pixel = background;
+        }
+
+        self.dt = DrawTarget::from_vec(width.max(0), height.max(0), pixels);
+        self.damage.invalidate();
+        // Layers are cropped to the screen they were painted on
+        self.layers.invalidate_all();
+    }
+
+    /// Clips the next drawings to the area of `clip_size` at `clip_point`,
+    /// reusing the current clip when it is the same one
+    fn set_clip(&mut self, clip_point: &Vector2D, clip_size: &Vector2D) {
+        let clip = [clip_point.x, clip_point.y, clip_size.x, clip_size.y];
+        if self.current_clip == Some(clip) {
+            return;
+        }
+        self.reset_clip();
+
+        // Clips aligned to whole pixels skip building a clip mask
+        let aligned = clip.iter().all(|value| value.fract() == 0.)
+            && *self.dt.get_transform() == Transform::identity();
+        if aligned {
+            self.dt.push_clip_rect(IntRect::new(
+                IntPoint::new(clip[0] as i32, clip[1] as i32),
+                IntPoint::new((clip[0] + clip[2]) as i32, (clip[1] + clip[3]) as i32),
+            ));
+        } else {
+            let mut pb_clip = PathBuilder::new();
+            pb_clip.rect(
+                clip_point.x as f32,
+                clip_point.y as f32,
+                clip_size.x as f32,
+                clip_size.y as f32,
+            );
+            pb_clip.close();
+            self.dt.push_clip(&pb_clip.finish());
+        }
+        self.current_clip = Some(clip);
+    }
+
+    /// Pops the clip kept from the last instruction, if any
+    ///
+    /// Drawing primitives leave their clip pushed on [`Raqote::dt`] so that
+    /// following instructions with the same clip can reuse it. This must be
+    /// called before drawing on [`Raqote::dt`] directly.
+    pub fn reset_clip(&mut self) {
+        if self.current_clip.take().is_some() {
+            self.dt.pop_clip();
+        }
+    }
+
+    /// Widget keys of the collection whose instructions changed on the
+    /// last frame drawn by [`Renderer::draw_collection`]
+    pub fn changed_keys(&self) -> &[usize] {
+        self.damage.changed_keys()
+    }
+
+    /// Forces the next frame to be painted in full
+    pub fn invalidate(&mut self) {
+        self.damage.invalidate();
+    }
+
+    /// Draws a render instruction or blits a layer
+    pub(crate) fn draw_item(&mut self, item: &DrawItem) {
+        match item {
+            DrawItem::Instruction(instruction) => self.draw(instruction),
+            DrawItem::RichText { instruction, spans } => self.draw_with_spans(instruction, spans),
+            DrawItem::Layer {
+                left,
+                top,
+                width,
+                height,
+                pixels,
+            } => {
+                self.reset_clip();
+                self.dt.draw_image_at(
+                    *left as f32,
+                    *top as f32,
+                    &raqote::Image {
+                        width: *width,
+                        height: *height,
+                        data: pixels,
+                    },
+                    &DrawOptions::new(),
+                );
+            }
+        }
+    }
+
+    /// Draws `instruction`, replacing its string by `spans` if it is a
+    /// [`RenderInstruction::DrawText`]
+    fn draw_with_spans(&mut self, instruction: &RenderInstruction, spans: &[TextSpan]) {
+        match instruction {
+            RenderInstruction::DrawText {
+                point,
+                clip_point,
+                clip_size,
+                ..
+            } => {
+                let spans: Vec<TextSpan> =
+                    spans.iter().map(|span| span.scaled(self.scale)).collect();
+                self.draw_spans(point, &spans, clip_point, clip_size);
+            }
+            _ => self.draw(instruction),
+        }
+    }
+
+    /// Draws `spans` instead of the strings of the text instructions of the
+    /// widget `key`, at their point and within their clip
+    ///
+    /// Render instructions only carry plain text, so this is how a widget
+    /// draws highlighted or bold words. The spans' font sizes are in logical
+    /// units, like the instructions.
+    pub fn set_rich_text(&mut self, key: usize, spans: Vec<TextSpan>) {
+        self.rich_texts.insert(key, spans);
+        self.damage.invalidate_key(key);
+        self.layers.invalidate(key);
+    }
+
+    /// Goes back to drawing the plain strings of the text instructions
+    /// of the widget `key`
+    pub fn remove_rich_text(&mut self, key: usize) {
+        if self.rich_texts.remove(&key).is_some() {
+            self.damage.invalidate_key(key);
+            self.layers.invalidate(key);
+        }
+    }
+
+    /// Marks whether the instructions of the widget `key` are rasterized
+    /// once into an offscreen layer, which is blitted on the following
+    /// frames until the instructions change
+    pub fn set_layer_cached(&mut self, key: usize, cached: bool) {
+        self.layers.set_cached(key, cached);
+    }
+
+    /// Drops the layer of the widget `key`, so that it is rasterized again
+    pub fn invalidate_layer(&mut self, key: usize) {
+        self.layers.invalidate(key);
+    }
+
+    /// Changes the memory budget, in bytes, of the offscreen layers
+    pub fn set_layer_budget(&mut self, budget: usize) {
+        self.layers.set_budget(budget);
+    }
+
+    /// Rasterizes again the layers of the cached keys whose instructions
+    /// changed, within `screen`
+    fn update_layers(
+        &mut self,
+        collection: &RenderInstructionCollection,
+        screen: &Rect,
+        rich_texts: &HashMap<usize, Vec<TextSpan>>,
+    ) {
+        self.layers.tick();
+        for (key, instructions) in collection.pairs.iter() {
+            if !self.layers.is_cached(*key) {
+                continue;
+            }
+            let hash = hash_instructions(instructions);
+            if self.layers.is_valid(*key, hash) {
+                continue;
+            }
+
+            // Instructions painting the whole target, such as clears, are
+            // left out of layers
+            let mut bounds = Some(Rect::new(0, 0, 0, 0));
+            for instruction in instructions {
+                bounds = bounds.and_then(|bounds| {
+                    Some(bounds.union(&instruction_bounds(instruction, self.scale)?))
+                });
+            }
+            match bounds.map(|bounds| bounds.intersection(screen)) {
+                Some(bounds) if !bounds.is_empty() => {
+                    let spans = rich_texts.get(key).map(|spans| &spans[..]);
+                    let dt = self.render_layer(instructions, spans, bounds);
+                    self.layers.insert(*key, Layer::new(hash, bounds, dt));
+                }
+                _ => self.layers.invalidate(*key),
+            }
+        }
+    }
+
+    /// Rasterizes `instructions`, with the rich text `spans` if any, into a
+    /// new draw target covering `bounds`
+    fn render_layer(
+        &mut self,
+        instructions: &[RenderInstruction],
+        spans: Option<&[TextSpan]>,
+        bounds: Rect,
+    ) -> DrawTarget {
+        self.reset_clip();
+        let damaged_region = self.damaged_region.take();
+        let mut layer = DrawTarget::new(bounds.width(), bounds.height());
+        std::mem::swap(&mut self.dt, &mut layer);
+        self.dt.set_transform(&Transform::create_translation(
+            -bounds.left as f32,
+            -bounds.top as f32,
         ));
+
+        for instruction in instructions {
+            match spans {
+                Some(spans) => self.draw_with_spans(instruction, spans),
+                None => self.draw(instruction),
+            }
+        }
+
+        self.reset_clip();
+        std::mem::swap(&mut self.dt, &mut layer);
+        self.damaged_region = damaged_region;
+        layer
+    }
+
+    /// Paints again the pixels within `region`, drawing only the
+    /// instructions whose bounds touch it
+    pub(crate) fn draw_region(
+        &mut self,
+        region: Rect,
+        items: &[(DrawItem, Option<Rect>)],
+    ) {
+        if region.is_empty() {
+            return;
+        }
+        self.reset_clip();
+        let mut pb_clip = PathBuilder::new();
+        pb_clip.rect(
+            region.left as f32,
+            region.top as f32,
+            region.width() as f32,
+            region.height() as f32,
+        );
+        pb_clip.close();
+        self.dt.push_clip(&pb_clip.finish());
+        self.damaged_region = Some(region);
+
+        for (item, bounds) in items {
+            if bounds.map_or(true, |bounds| bounds.intersects(&region)) {
+                self.draw_item(item);
+            }
+        }
+
+        self.damaged_region = None;
+        self.reset_clip();
+        self.dt.pop_clip();
     }
 
     fn draw_point(
@@ -218,15 +1077,7 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
-            clip_point.x as f32,
-            clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
+        self.set_clip(clip_point, clip_size);
         let mut pb = PathBuilder::new();
         pb.move_to(point_a.x as f32, point_a.y as f32);
         pb.line_to(point_b.x as f32, point_b.y as f32);
@@ -239,14 +1090,13 @@ impl Raqote {
             &StrokeStyle {
                 cap: LineCap::Square,
                 join: LineJoin::Bevel,
-                width: 10.,
+                width: 10. * self.scale as f32,
                 miter_limit: 0.,
-                dash_array: vec![1., 1.],
+                dash_array: vec![self.scale as f32, self.scale as f32],
                 dash_offset: 0.,
             },
             &DrawOptions::new(),
         );
-        self.dt.pop_clip();
     }
 
     fn draw_arc(
@@ -259,15 +1109,7 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
-            clip_point.x as f32,
-            clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
+        self.set_clip(clip_point, clip_size);
         let mut pb = PathBuilder::new();
         pb.move_to(point.x as f32, point.y as f32);
         pb.arc(
@@ -286,7 +1128,6 @@ impl Raqote {
             )),
             &DrawOptions::new(),
         );
-        self.dt.pop_clip();
     }
 
     fn draw_circle(
@@ -297,15 +1138,7 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
-            clip_point.x as f32,
-            clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
+        self.set_clip(clip_point, clip_size);
         let mut pb = PathBuilder::new();
         pb.move_to(point.x as f32, point.y as f32);
         pb.arc(point.x as f32, point.y as f32, r as f32, 0., 7.);
@@ -317,7 +1150,6 @@ impl Raqote {
             )),
             &DrawOptions::new(),
         );
-        self.dt.pop_clip();
     }
 
     fn draw_rectangle(
@@ -328,15 +1160,7 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
-            clip_point.x as f32,
-            clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
+        self.set_clip(clip_point, clip_size);
         let mut pb = PathBuilder::new();
         pb.rect(point.x as f32, point.y as f32, size.x as f32, size.y as f32);
         pb.close();
@@ -348,7 +1172,6 @@ impl Raqote {
             )),
             &DrawOptions::new(),
         );
-        self.dt.pop_clip();
     }
 
     fn draw_triangle(
@@ -360,15 +1183,7 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
-            clip_point.x as f32,
-            clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
+        self.set_clip(clip_point, clip_size);
         let mut pb = PathBuilder::new();
 
         pb.move_to(point_a.x as f32, point_a.y as f32);
@@ -383,7 +1198,6 @@ impl Raqote {
             )),
             &DrawOptions::new(),
         );
-        self.dt.pop_clip();
     }
 
     fn draw_image(
@@ -394,59 +1208,47 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
-            clip_point.x as f32,
-            clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
-        let rgba = open(path).unwrap().into_rgba8();
-        let img: Vec<u32> = rgba
-            .pixels()
-            .map(|p| {
-                ((p[3] as u32) << 24) | ((p[0] as u32) << 16) | ((p[1] as u32) << 8) | (p[2] as u32)
-            })
-            .collect();
+        self.set_clip(clip_point, clip_size);
+        let image = open(path).unwrap();
+        let data = image_to_argb(&image);
+        let img = raqote::Image {
+            width: image.width() as i32,
+            height: image.height() as i32,
+            data: &data,
+        };
         match options {
-            DrawImageOptions::OriginalSize => self.dt.draw_image_at(
-                point.x as f32,
-                point.y as f32,
-                &raqote::Image {
-                    width: rgba.width() as i32,
-                    height: rgba.height() as i32,
-                    data: &img,
-                },
-                &DrawOptions::new(),
-            ),
+            DrawImageOptions::OriginalSize => {
+                self.dt
+                    .draw_image_at(point.x as f32, point.y as f32, &img, &DrawOptions::new())
+            }
             DrawImageOptions::Resize { width, height } => self.dt.draw_image_with_size_at(
                 *width as f32,
                 *height as f32,
                 point.x as f32,
                 point.y as f32,
-                &raqote::Image {
-                    width: rgba.width() as i32,
-                    height: rgba.height() as i32,
-                    data: &img,
-                },
+                &img,
                 &DrawOptions::new(),
             ),
             DrawImageOptions::ResizeMultiplyer { mult } => self.dt.draw_image_with_size_at(
-                rgba.width() as f32 * *mult as f32,
-                rgba.height() as f32 * *mult as f32,
+                img.width as f32 * *mult as f32,
+                img.height as f32 * *mult as f32,
                 point.x as f32,
                 point.y as f32,
-                &raqote::Image {
-                    width: rgba.width() as i32,
-                    height: rgba.height() as i32,
-                    data: &img,
-                },
+                &img,
                 &DrawOptions::new(),
             ),
         }
-        self.dt.pop_clip();
+    }
+
+    /// Copies the pixels drawn so far to an image, with straight alpha,
+    /// to be encoded by [`image`](`crate`)
+    pub fn to_image(&self) -> RgbaImage {
+        argb_to_image(
+            self.dt.get_data(),
+            self.dt.width() as u32,
+            self.dt.height() as u32,
+        )
+        .unwrap()
     }
 
     fn draw_text(
@@ -458,33 +1260,286 @@ impl Raqote {
         clip_point: &Vector2D,
         clip_size: &Vector2D,
     ) {
-        let mut pb_clip = PathBuilder::new();
-        pb_clip.rect(
+        let spans = [TextSpan::new(string, TextStyle::new(font_size, color.clone()))];
+        self.draw_spans(point, &spans, clip_point, clip_size);
+    }
+
+    /// Lays out `spans` with their first baseline starting at `point`,
+    /// the same way they would be drawn by [`Raqote::draw_rich_text`]
+    pub fn layout_text(&mut self, point: &Vector2D, spans: &[TextSpan]) -> TextLayout {
+        TextLayout::new(&mut self.fonts, (point.x as f32, point.y as f32), spans)
+    }
+
+    /// Gets the grapheme index of `string`, drawn like [`RenderInstruction::DrawText`],
+    /// closest to `position`
+    pub fn text_hit_test(
+        &mut self,
+        point: &Vector2D,
+        font_size: usize,
+        string: &str,
+        position: &Vector2D,
+    ) -> usize {
+        let spans = [TextSpan::new(string, TextStyle::new(font_size, Color::new(0, 0, 0, 0)))];
+        self.layout_text(point, &spans)
+            .hit_test(position.x as f32, position.y as f32)
+    }
+
+    /// Gets the point and size of the caret placed before the grapheme
+    /// `index` of `string`, drawn like [`RenderInstruction::DrawText`]
+    pub fn text_caret_rect(
+        &mut self,
+        point: &Vector2D,
+        font_size: usize,
+        string: &str,
+        index: usize,
+    ) -> (Vector2D, Vector2D) {
+        let spans = [TextSpan::new(string, TextStyle::new(font_size, Color::new(0, 0, 0, 0)))];
+        self.layout_text(point, &spans).caret_rect(index)
+    }
+
+    /// Gets the points and sizes of the rectangles covering the graphemes
+    /// within `range` of `string`, drawn like [`RenderInstruction::DrawText`]
+    pub fn text_selection_rects(
+        &mut self,
+        point: &Vector2D,
+        font_size: usize,
+        string: &str,
+        range: std::ops::Range<usize>,
+    ) -> Vec<(Vector2D, Vector2D)> {
+        let spans = [TextSpan::new(string, TextStyle::new(font_size, Color::new(0, 0, 0, 0)))];
+        self.layout_text(point, &spans).selection_rects(range)
+    }
+
+    /// Draws spans of text with mixed fonts, sizes, colors and decorations,
+    /// with their first baseline starting at `point`
+    ///
+    /// Everything is given in logical units and drawn at [`Raqote::scale`].
+    /// Spans drawn this way are not part of the collection, so they are
+    /// painted over by the next frame; widgets use [`Raqote::set_rich_text`]
+    /// instead.
+    pub fn draw_rich_text(
+        &mut self,
+        point: &Vector2D,
+        spans: &[TextSpan],
+        clip_point: &Vector2D,
+        clip_size: &Vector2D,
+    ) {
+        let spans: Vec<TextSpan> = spans.iter().map(|span| span.scaled(self.scale)).collect();
+        self.draw_spans(
+            &scale_vector(point, self.scale),
+            &spans,
+            &scale_vector(clip_point, self.scale),
+            &scale_vector(clip_size, self.scale),
+        );
+    }
+
+    /// Draws `spans` with their first baseline starting at `point`, all in
+    /// physical pixels
+    fn draw_spans(
+        &mut self,
+        point: &Vector2D,
+        spans: &[TextSpan],
+        clip_point: &Vector2D,
+        clip_size: &Vector2D,
+    ) {
+        self.set_clip(clip_point, clip_size);
+        let layout = self.layout_text(point, spans);
+        // Bounds of the clip on the draw target, for the glyphs blended
+        // without going through raqote
+        let transform = self.dt.get_transform();
+        let top_left = transform.transform_point(raqote::Point::new(
             clip_point.x as f32,
             clip_point.y as f32,
-            clip_size.x as f32,
-            clip_size.y as f32,
-        );
-        pb_clip.close();
-        self.dt.push_clip(&pb_clip.finish());
-        let font = SystemSource::new()
-            .select_best_match(&[FamilyName::SansSerif], &Properties::new())
-            .unwrap()
-            .load()
-            .unwrap();
-
-        self.dt.draw_text(
-            &font,
-            font_size as f32,
-            string,
-            raqote::Point::new(point.x as f32, point.y as f32),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                color.a, color.r, color.g, color.b,
-            )),
-            &DrawOptions::new(),
+        ));
+        let bottom_right = transform.transform_point(raqote::Point::new(
+            (clip_point.x + clip_size.x) as f32,
+            (clip_point.y + clip_size.y) as f32,
+        ));
+        let mut clip = Rect::from_edges(
+            top_left.x as f64,
+            top_left.y as f64,
+            bottom_right.x as f64,
+            bottom_right.y as f64,
         );
+        if let Some(region) = self.damaged_region {
+            clip = clip.intersection(&region);
+        }
+        self.draw_layout(&layout, (clip.left, clip.top, clip.right, clip.bottom));
+    }
 
-        self.dt.pop_clip();
+    fn draw_layout(&mut self, layout: &TextLayout, clip: (i32, i32, i32, i32)) {
+        for line in layout.lines.iter() {
+            for run in line.runs.iter() {
+                let color = &run.style.color;
+                let solid = SolidSource::from_unpremultiplied_argb(color.a, color.r, color.g, color.b);
+                let source = Source::Solid(solid);
+                let size = run.style.font_size as f32;
+                for glyph in run.glyphs.iter() {
+                    // Bitmap glyphs, such as emoji, are drawn as images
+                    if let Some(bitmap) =
+                        self.color_glyphs
+                            .bitmap(run.font_id, &run.font, glyph.id, size)
+                    {
+                        self.dt.draw_image_at(
+                            (glyph.position.x + bitmap.left).round(),
+                            (glyph.position.y + bitmap.top).round(),
+                            &raqote::Image {
+                                width: bitmap.width,
+                                height: bitmap.height,
+                                data: &bitmap.data,
+                            },
+                            &DrawOptions::new(),
+                        );
+                        continue;
+                    }
+
+                    // Layered glyphs stack outlines filled with palette colors
+                    let layers = self
+                        .color_glyphs
+                        .layers(run.font_id, &run.font, glyph.id)
+                        .unwrap_or_else(|| {
+                            vec![ColorLayer {
+                                glyph_id: glyph.id,
+                                color: None,
+                            }]
+                        });
+                    let position = self.dt.get_transform().transform_point(glyph.position);
+                    for layer in layers {
+                        let layer_solid = match layer.color {
+                            Some((a, r, g, b)) => SolidSource::from_unpremultiplied_argb(a, r, g, b),
+                            None => solid,
+                        };
+                        let (x, y, mask) = self.glyphs.get(
+                            run.font_id,
+                            &run.font,
+                            size,
+                            layer.glyph_id,
+                            (position.x, position.y),
+                        );
+                        match mask {
+                            _ if mask.is_empty() => (),
+                            GlyphMask::Coverage(mask) => {
+                                self.dt.mask(&Source::Solid(layer_solid), x, y, mask)
+                            }
+                            GlyphMask::Subpixel { .. } => {
+                                blend_subpixel_mask(&mut self.dt, x, y, mask, layer_solid, clip)
+                            }
+                        }
+                    }
+                }
+
+                // Decorations follow the metrics of the run's own font
+                let metrics = run.font.metrics();
+                let scale = run.style.font_size as f32 / metrics.units_per_em as f32;
+                let thickness = (metrics.underline_thickness * scale).max(1.);
+                let (x, width) = run.extent();
+                if run.style.underline {
+                    let y = line.baseline - metrics.underline_position * scale;
+                    self.dt.fill_rect(
+                        x,
+                        y - thickness / 2.,
+                        width,
+                        thickness,
+                        &source,
+                        &DrawOptions::new(),
+                    );
+                }
+                if run.style.strikethrough {
+                    let y = line.baseline - metrics.x_height * scale / 2.;
+                    self.dt.fill_rect(
+                        x,
+                        y - thickness / 2.,
+                        width,
+                        thickness,
+                        &source,
+                        &DrawOptions::new(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draws `collection` on a draw target of `width` by `height` pixels,
+    /// without presenting it, returning whether anything was drawn
+    ///
+    /// This is what [`Renderer::draw_collection`] does before updating the
+    /// window, so it can also be used headless. The instructions are given
+    /// in logical units, and drawn at [`Raqote::scale`].
+    pub fn render_collection(
+        &mut self,
+        collection: &RenderInstructionCollection,
+        width: i32,
+        height: i32,
+    ) -> bool {
+        if width != self.dt.width() || height != self.dt.height() {
+            self.resize(width, height);
+        }
+
+        // Finds the area that changed since the previous frame, in physical
+        // pixels, before paying for scaling the instructions
+        let screen = Rect::new(0, 0, self.dt.width(), self.dt.height());
+        let damage = self.damage.update(collection);
+        if damage == Damage::None {
+            return false;
+        }
+
+        // Everything after this point works in physical pixels
+        let scaled;
+        let collection = if self.scale != 1. {
+            scaled = scale_collection(collection, self.scale);
+            &scaled
+        } else {
+            collection
+        };
+
+        let region = match damage {
+            Damage::Region(region) => region.intersection(&screen),
+            _ => screen,
+        };
+
+        let rich_texts = std::mem::take(&mut self.rich_texts);
+        self.update_layers(collection, &screen, &rich_texts);
+        let layers = std::mem::take(&mut self.layers);
+
+        // Culls the instructions that paint nothing within the region,
+        // either because they are off-screen or outside their own clip
+        let mut items = draw_items(collection, &layers, &rich_texts, self.scale);
+        let total = items.len();
+        items.retain(|(_, bounds)| bounds.map_or(true, |bounds| bounds.intersects(&region)));
+        self.cull_stats = CullStats {
+            drawn: items.len(),
+            culled: total - items.len(),
+        };
+
+        // Tiles are drawn in parallel, leaving nothing else to draw
+        #[cfg(feature = "parallel")]
+        let damage = match self.tiled {
+            Some(tiled) => {
+                let text_options = self.text_render_options();
+                let scale = self.scale;
+                tiles::draw_tiled(&mut self.dt, region, &items, tiled, text_options, scale);
+                Damage::None
+            }
+            None => damage,
+        };
+
+        match damage {
+            Damage::None => (),
+            Damage::Full => {
+                // Loop to iterate over all the visible render instructions
+                for (item, _bounds) in items.iter() {
+                    // Draw the render instruction
+                    self.draw_item(item);
+                }
+            }
+            Damage::Region(_) => self.draw_region(region, &items),
+        }
+
+        self.reset_clip();
+        drop(items);
+        self.layers = layers;
+        self.rich_texts = rich_texts;
+        true
     }
 
     pub fn draw(&mut self, instruction: &RenderInstruction) {
@@ -552,6 +1607,56 @@ impl Raqote {
         }
     }
 }
+/// Gets a copy of a collection with its instructions scaled by `scale`
+fn scale_collection(
+    collection: &RenderInstructionCollection,
+    scale: f64,
+) -> RenderInstructionCollection {
+    let mut scaled = RenderInstructionCollection::new();
+    for (key, instructions) in collection.pairs.iter() {
+        let instructions = instructions
+            .iter()
+            .map(|instruction| scale_instruction(instruction, scale))
+            .collect();
+        scaled.pairs.insert(*key, instructions);
+    }
+    scaled
+}
+
+/// Flattens the instructions of a collection, in drawing order, along with
+/// their bounds, replacing the instructions of the keys with a layer by
+/// the layer itself, and the text of the keys with rich text by its spans
+fn draw_items<'a>(
+    collection: &'a RenderInstructionCollection,
+    layers: &'a LayerCache,
+    rich_texts: &'a HashMap<usize, Vec<TextSpan>>,
+    scale: f64,
+) -> Vec<(DrawItem<'a>, Option<Rect>)> {
+    let mut items = Vec::new();
+    for (key, instructions) in collection.pairs.iter() {
+        match layers.get(*key) {
+            Some(layer) if layers.is_cached(*key) => items.push((
+                DrawItem::Layer {
+                    left: layer.bounds.left,
+                    top: layer.bounds.top,
+                    width: layer.dt.width(),
+                    height: layer.dt.height(),
+                    pixels: layer.dt.get_data(),
+                },
+                Some(layer.bounds),
+            )),
+            _ => items.extend(instructions.iter().map(|instruction| {
+                let item = match rich_texts.get(key) {
+                    Some(spans) => DrawItem::RichText { instruction, spans },
+                    None => DrawItem::Instruction(instruction),
+                };
+                (item, instruction_bounds(instruction, scale))
+            })),
+        }
+    }
+    items
+}
+
 impl Renderer<DisplayMinifb, EventClient> for Raqote {
     fn map_events(event_client: EventClient) -> event::Event {
         match event_client {
@@ -614,7 +1719,32 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
     }
 
     fn detect_display_events(queue: &mut Queue<event::Event>, display: &mut DisplayMinifb) {
-        if display.is_open() && !display.display.is_key_down(minifb::Key::Escape) {
+        display.input_detected = false;
+        // A close request that wasn't vetoed since the last call goes through
+        let vetoed = display.close_control.borrow_mut().take_veto();
+        if display.window_state.close_requested {
+            if !vetoed {
+                display.close();
+            } else if let Err(e) = display.cancel_close() {
+                // The window is gone, so the close goes through after all
+                display.last_error = Some(DisplayError::Window(e));
+                display.close();
+            }
+        }
+        if !display.is_open() {
+            return;
+        }
+
+        let quit_pressed = display
+            .quit_key
+            .map_or(false, |key| display.display.is_key_pressed(key, minifb::KeyRepeat::No));
+        if quit_pressed || !display.display.is_open() {
+            display.window_state.close_requested = true;
+            display.close_control.borrow_mut().set_requested(true);
+            display.input_detected = true;
+        }
+
+        if display.display.is_open() {
             //Mouse
             let left_button_down = display.display.get_mouse_down(minifb::MouseButton::Left);
             let right_button_down = display.display.get_mouse_down(minifb::MouseButton::Right);
@@ -622,13 +1752,59 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
             let mouse_position = display.display.get_mouse_pos(minifb::MouseMode::Pass);
             //Window
             let window_size = display.display.get_size();
+            let position = display.display.get_position();
+            // minifb can't tell whether the window is minimized, but such
+            // windows have no area or, on Windows, are moved far offscreen
+            let minimized = window_size.0 == 0
+                || window_size.1 == 0
+                || (position.0 <= MINIMIZED_POSITION && position.1 <= MINIMIZED_POSITION);
+            let focused = display.display.is_active();
 
-            if window_size != display.window_state.window_size {
-                queue.enqueue(Self::map_events(EventClient::WindowResize {
-                    width: window_size.0 as u32,
-                    height: window_size.1 as u32,
-                }));
+            let mut window_events = display.window_events.borrow_mut();
+            if focused != display.window_state.focused {
+                if focused {
+                    window_events.push_back(WindowEvent::FocusGained);
+                } else {
+                    window_events.push_back(WindowEvent::FocusLost);
+                }
+                display.window_state.focused = focused;
+                display.input_detected = true;
+            }
+
+            if minimized != display.window_state.minimized {
+                if minimized {
+                    window_events.push_back(WindowEvent::Minimized);
+                } else {
+                    window_events.push_back(WindowEvent::Restored);
+                }
+                display.window_state.minimized = minimized;
+                display.input_detected = true;
+            }
+
+            // The size and position of a minimized window mean nothing
+            if !minimized && position != display.window_state.position {
+                window_events.push_back(WindowEvent::Moved {
+                    x: display.to_logical(position.0 as f32).round() as i32,
+                    y: display.to_logical(position.1 as f32).round() as i32,
+                });
+                display.window_state.position = position;
+                display.input_detected = true;
+            }
+            drop(window_events);
+
+            if !minimized && window_size != display.window_state.window_size {
+                if display.fixed_resolution.is_some() {
+                    // The contents keep their size, but must fit the window again
+                    display.needs_redraw = true;
+                } else {
+                    let (width, height) = display.surface_size();
+                    queue.enqueue(Self::map_events(EventClient::WindowResize {
+                        width: display.to_logical(width as f32).round() as u32,
+                        height: display.to_logical(height as f32).round() as u32,
+                    }));
+                }
                 display.window_state.window_size = window_size;
+                display.input_detected = true;
             }
 
             if mouse_position != Some(display.mouse_state.mouse_pos) {
@@ -638,30 +1814,51 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
                     x = mouse.0;
                     y = mouse.1;
                 });
-                let window_width: f32 = display.display.get_size().0 as f32;
-                let window_weight: f32 = display.display.get_size().1 as f32;
-                if x < 0f32 || x > window_width || y < 0f32 || y > window_weight {
+                display.mouse_state.mouse_pos = (x, y);
+                // Positions over the contents, outside of any letterbox bars
+                let surface = display.to_surface(x, y);
+                let (window_width, window_weight) = display.mouse_area();
+                let outside = surface.map_or(true, |(x, y)| {
+                    x < 0f32 || x > window_width || y < 0f32 || y > window_weight
+                });
+                if outside {
                     //mouse out of window
                     if display.mouse_state.mouse_on_window {
                         display.mouse_state.mouse_on_window = false;
                         queue.enqueue(Self::map_events(EventClient::MouseLeft));
                     }
                 }
-                if x > 0f32 && x < window_width && y > 0f32 && y < window_weight {
-                    //mouse inside window
-                    queue.enqueue(Self::map_events(EventClient::MouseMove { x: x, y: y }));
+                let inside = surface.filter(|(x, y)| {
+                    *x > 0f32 && *x < window_width && *y > 0f32 && *y < window_weight
+                });
+                if let Some((x, y)) = inside {
+                    //mouse inside window, in logical units
+                    queue.enqueue(Self::map_events(EventClient::MouseMove {
+                        x: display.to_logical(x),
+                        y: display.to_logical(y),
+                    }));
                     if !display.mouse_state.mouse_on_window {
                         display.mouse_state.mouse_on_window = true;
                         queue.enqueue(Self::map_events(EventClient::MouseEntered));
                     }
                 }
-                display.mouse_state.mouse_pos = (x, y);
+                display.input_detected = true;
+            }
+            display.apply_cursor();
+
+            //Menu
+            if let Some(id) = display.display.is_menu_pressed() {
+                let menu_activated = WindowEvent::MenuActivated { id };
+                display.window_events.borrow_mut().push_back(menu_activated);
+                display.input_detected = true;
             }
 
             display.display.get_scroll_wheel().map(|scroll| {
+                display.input_detected = true;
+                // The wheel deltas of minifb are in physical pixels
                 queue.enqueue(Self::map_events(EventClient::Scroll {
-                    x: scroll.0,
-                    y: scroll.1,
+                    x: display.to_logical(scroll.0),
+                    y: display.to_logical(scroll.1),
                 }));
             });
 
@@ -672,6 +1869,7 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
                     queue.enqueue(Self::map_events(EventClient::LeftClickRelease));
                 }
                 display.mouse_state.button_left = left_button_down;
+                display.input_detected = true;
             }
             if right_button_down != display.mouse_state.button_right {
                 if right_button_down {
@@ -680,6 +1878,7 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
                     queue.enqueue(Self::map_events(EventClient::RightClickRelease));
                 }
                 display.mouse_state.button_right = right_button_down;
+                display.input_detected = true;
             }
             if middle_button_down != display.mouse_state.button_middle {
                 if middle_button_down {
@@ -688,2391 +1887,29 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
                     queue.enqueue(Self::map_events(EventClient::MiddleClickRelease));
                 }
                 display.mouse_state.button_middle = middle_button_down;
+                display.input_detected = true;
             }
 
             //Keyboard
             //Check for key modifiers
-            let mut shift = false;
-            let mut control = false;
-            let mut alt = false;
-            let mut logo = false;
-
-            if display.display.is_key_down(minifb::Key::LeftShift)
-                || display.display.is_key_down(minifb::Key::RightShift)
-            {
-                shift = true;
-            }
-
-            if display.display.is_key_down(minifb::Key::LeftCtrl)
-                || display.display.is_key_down(minifb::Key::RightCtrl)
-            {
-                control = true;
-            }
-
-            if display.display.is_key_down(minifb::Key::LeftAlt)
-                || display.display.is_key_down(minifb::Key::RightAlt)
-            {
-                alt = true;
-            }
-            //TODO: Windows Key
-            if display.display.is_key_down(minifb::Key::LeftCtrl)
-                || display.display.is_key_down(minifb::Key::RightCtrl)
-            {
-                logo = true;
-            }
+            let modifiers = Modifiers::from_window(&display.display);
 
             //-----Get Key Press--
             display
                 .display
                 .get_keys_pressed(minifb::KeyRepeat::Yes)
                 .map(|keys| {
-                    for t in keys {
-                        match t {
-                            minifb::Key::Key0 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key0,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key1 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key1,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key2 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key2,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key3 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key3,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key4 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key4,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key5 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key5,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key6 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key6,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key7 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key7,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key8 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key8,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Key9 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Key9,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::A => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::A,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::B => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::B,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::C => {
-                                if control {
-                                    //Copy Event
-                                    queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                        key_code: key_code::KeyCode::Copy,
-                                        modifiers: event::ModifiersState {
-                                            shift,
-                                            control,
-                                            alt,
-                                            logo,
-                                        },
-                                    }))
-                                } else {
-                                    queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                        key_code: key_code::KeyCode::C,
-                                        modifiers: event::ModifiersState {
-                                            shift,
-                                            control,
-                                            alt,
-                                            logo,
-                                        },
-                                    }))
-                                }
-                            }
-
-                            minifb::Key::D => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::D,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::E => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::E,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::G => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::G,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::H => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::H,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::I => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::I,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::J => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::J,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::K => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::K,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::L => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::L,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::M => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::M,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::N => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::N,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::O => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::O,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::P => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::P,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Q => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Q,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::R => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::R,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::S => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::S,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::T => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::T,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::U => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::U,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::V => {
-                                if control {
-                                    //Paste event
-                                    queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                        key_code: key_code::KeyCode::Paste,
-                                        modifiers: event::ModifiersState {
-                                            shift,
-                                            control,
-                                            alt,
-                                            logo,
-                                        },
-                                    }))
-                                } else {
-                                    queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                        key_code: key_code::KeyCode::V,
-                                        modifiers: event::ModifiersState {
-                                            shift,
-                                            control,
-                                            alt,
-                                            logo,
-                                        },
-                                    }))
-                                }
-                            }
-
-                            minifb::Key::W => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::W,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::X => {
-                                if control {
-                                    //Cut event
-                                    queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                        key_code: key_code::KeyCode::Cut,
-                                        modifiers: event::ModifiersState {
-                                            shift,
-                                            control,
-                                            alt,
-                                            logo,
-                                        },
-                                    }))
-                                } else {
-                                    queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                        key_code: key_code::KeyCode::X,
-                                        modifiers: event::ModifiersState {
-                                            shift,
-                                            control,
-                                            alt,
-                                            logo,
-                                        },
-                                    }))
-                                }
-                            }
-                            minifb::Key::Y => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Y,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Z => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Z,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F1 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F1,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F2 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F2,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F3 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F3,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F4 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F4,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F5 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F5,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F6 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F6,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F7 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F7,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F8 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F8,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F9 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F9,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F10 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F10,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F11 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F11,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F12 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F12,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F13 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F13,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F14 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F14,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::F15 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::F15,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Down => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Down,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Left => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Left,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Right => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Right,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Up => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Up,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Apostrophe => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Apostrophe,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            // minifb::Key::Backquote => queue.enqueue(Self::map_events(EventClient::KeyPressed{key_code:key_code::KeyCode::Backquote, modifiers:event::ModifiersState{shift,control,alt,logo}})),
-                            minifb::Key::Backslash => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Backslash,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Comma => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Comma,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Equal => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Equals,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::LeftBracket => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::LBracket,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Minus => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Minus,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Period => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Period,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::RightBracket => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::RBracket,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Semicolon => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Semicolon,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Slash => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Slash,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Backspace => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Backspace,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Delete => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Delete,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::End => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::End,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Enter => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Enter,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Escape => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Escape,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Home => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Home,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Insert => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Insert,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            //TODO: Change mapping MENU
-                            minifb::Key::Menu => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::AbntC1,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::PageDown => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::PageDown,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::PageUp => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::PageUp,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Pause => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Pause,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Space => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Space,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::Tab => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Tab,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumLock => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numlock,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            //TODO: Missing CapsLock code hyber
-                            //minifb::Key::CapsLock => queue.enqueue(Self::map_events(EventClient::KeyPressed{key_code:key_code::KeyCode::CapsLock, modifiers:event::ModifiersState{shift,control,alt,logo}})),
-                            minifb::Key::ScrollLock => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Scroll,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::LeftShift => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::LShift,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::RightShift => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::RShift,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::LeftCtrl => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::LControl,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::RightCtrl => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::RControl,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad0 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad0,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad1 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad1,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad2 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad2,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad3 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad3,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad4 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad4,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad5 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad5,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad6 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad6,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad7 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad7,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad8 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad8,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPad9 => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Numpad9,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPadDot => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::Period,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPadSlash => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::NumpadDivide,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPadAsterisk => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::NumpadMultiply,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPadMinus => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::NumpadSubtract,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPadPlus => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::NumpadAdd,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::NumPadEnter => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::NumpadEnter,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::LeftAlt => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::LAlt,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            minifb::Key::RightAlt => {
-                                queue.enqueue(Self::map_events(EventClient::KeyPressed {
-                                    key_code: key_code::KeyCode::RAlt,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                            _ => (),
-                        }
+                    display.input_detected |= !keys.is_empty();
+                    for event in key_events(&keys, true, modifiers) {
+                        queue.enqueue(Self::map_events(event));
                     }
                 });
 
             //-----Get Key released -
             display.display.get_keys_released().map(|keys| {
-                for t in keys {
-                    match t {
-                        minifb::Key::Key0 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key0,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key1 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key1,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key2 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key2,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key3 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key3,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key4 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key4,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key5 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key5,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key6 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key6,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key7 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key7,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key8 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key8,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Key9 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Key9,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::A => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::A,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::B => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::B,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::C => {
-                            if control {
-                                //Copy Event
-                                queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                    key_code: key_code::KeyCode::Copy,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            } else {
-                                queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                    key_code: key_code::KeyCode::C,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                        }
-
-                        minifb::Key::D => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::D,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::E => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::E,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::G => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::G,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::H => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::H,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::I => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::I,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::J => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::J,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::K => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::K,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::L => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::L,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::M => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::M,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::N => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::N,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::O => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::O,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::P => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::P,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Q => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Q,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::R => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::R,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::S => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::S,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::T => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::T,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::U => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::U,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::V => {
-                            if control {
-                                //Paste event
-                                queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                    key_code: key_code::KeyCode::Paste,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            } else {
-                                queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                    key_code: key_code::KeyCode::V,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                        }
-
-                        minifb::Key::W => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::W,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::X => {
-                            if control {
-                                //Cut event
-                                queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                    key_code: key_code::KeyCode::Cut,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            } else {
-                                queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                    key_code: key_code::KeyCode::X,
-                                    modifiers: event::ModifiersState {
-                                        shift,
-                                        control,
-                                        alt,
-                                        logo,
-                                    },
-                                }))
-                            }
-                        }
-                        minifb::Key::Y => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Y,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Z => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Z,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F1 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F1,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F2 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F2,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F3 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F3,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F4 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F4,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F5 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F5,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F6 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F6,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F7 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F7,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F8 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F8,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F9 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F9,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F10 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F10,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F11 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F11,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F12 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F12,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F13 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F13,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F14 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F14,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::F15 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::F15,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Down => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Down,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Left => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Left,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Right => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Right,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Up => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Up,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Apostrophe => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Apostrophe,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        // minifb::Key::Backquote => queue.enqueue(Self::map_events(EventClient::KeyReleased{key_code:key_code::KeyCode::Backquote, modifiers:event::ModifiersState{shift,control,alt,logo}})),
-                        minifb::Key::Backslash => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Backslash,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Comma => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Comma,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Equal => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Equals,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::LeftBracket => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::LBracket,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Minus => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Minus,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Period => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Period,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::RightBracket => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::RBracket,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Semicolon => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Semicolon,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Slash => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Slash,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Backspace => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Backspace,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Delete => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Delete,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::End => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::End,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Enter => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Enter,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Escape => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Escape,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Home => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Home,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Insert => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Insert,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        //TODO: Change mapping MENU
-                        minifb::Key::Menu => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::AbntC1,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::PageDown => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::PageDown,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::PageUp => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::PageUp,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Pause => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Pause,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Space => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Space,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::Tab => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Tab,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumLock => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numlock,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        //TODO: Missing CapsLock code hyber
-                        //minifb::Key::CapsLock => queue.enqueue(Self::map_events(EventClient::KeyReleased{key_code:key_code::KeyCode::CapsLock, modifiers:event::ModifiersState{shift,control,alt,logo}})),
-                        minifb::Key::ScrollLock => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Scroll,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::LeftShift => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::LShift,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::RightShift => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::RShift,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::LeftCtrl => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::LControl,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::RightCtrl => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::RControl,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad0 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad0,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad1 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad1,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad2 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad2,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad3 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad3,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad4 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad4,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad5 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad5,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad6 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad6,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad7 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad7,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad8 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad8,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPad9 => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Numpad9,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPadDot => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::Period,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPadSlash => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::NumpadDivide,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPadAsterisk => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::NumpadMultiply,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPadMinus => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::NumpadSubtract,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPadPlus => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::NumpadAdd,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::NumPadEnter => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::NumpadEnter,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::LeftAlt => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::LAlt,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        minifb::Key::RightAlt => {
-                            queue.enqueue(Self::map_events(EventClient::KeyReleased {
-                                key_code: key_code::KeyCode::RAlt,
-                                modifiers: event::ModifiersState {
-                                    shift,
-                                    control,
-                                    alt,
-                                    logo,
-                                },
-                            }))
-                        }
-                        _ => (),
-                    }
+                display.input_detected |= !keys.is_empty();
+                for event in key_events(&keys, false, modifiers) {
+                    queue.enqueue(Self::map_events(event));
                 }
             });
         }
@@ -3086,32 +1923,271 @@ impl Renderer<DisplayMinifb, EventClient> for Raqote {
         collection: &RenderInstructionCollection,
         display: &mut DisplayMinifb,
     ) {
-        // Gets the current display size
-        let size = display.get_size();
-        if size.x as i32 != self.dt.width() || size.y as i32 != self.dt.height() {
-            self.dt = DrawTarget::new(size.x as i32, size.y as i32);
+        // There is nothing to present on a window closed by the user
+        if !display.display.is_open() {
+            return;
         }
+        let frame_start = Instant::now();
+        // The pacer keeps the time, instead of the update rate of minifb
+        display.set_paced(self.pacer.rate() != FrameRate::Unlimited);
 
-        // Loop to iterate over all the render instructions
-        for (_key, instructions) in collection.pairs.iter() {
-            for instruction in instructions {
-                /// Draw the render instruction
-                self.draw(instruction);
-            }
+        // Gets the current display size, in physical pixels
+        self.set_scale(display.scale_factor);
+        let (width, height) = display.surface_size();
+        let drawn = self.render_collection(collection, width as i32, height as i32);
+        if !drawn && !display.needs_redraw {
+            // The window already shows this frame, so only its events are pumped
+            display.update();
+            self.pacer.end_frame(frame_start, false);
+            self.pacer.wait(display.input_detected);
+            return;
         }
 
-        /// Renders the buffer
-        display
-            .display
-            .update_with_buffer(self.dt.get_data(), size.x as usize, size.y as usize)
-            .unwrap();
+        // Transparent windows may take colors not multiplied by their alpha
+        let buffer = if display.is_transparent() && display.present_alpha == PresentAlpha::Straight
+        {
+            unpremultiply_argb(self.dt.get_data(), &mut self.present_buffer);
+            &self.present_buffer[..]
+        } else {
+            self.dt.get_data()
+        };
+
+        // Renders the buffer
+        display.display.update_with_buffer(buffer, width, height).unwrap();
+        display.needs_redraw = false;
+        self.pacer.end_frame(frame_start, true);
+        self.pacer.wait(true);
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const WIDTH: i32 = 120;
+    const HEIGHT: i32 = 60;
+
+    fn text(string: &str) -> RenderInstruction {
+        RenderInstruction::DrawText {
+            point: Vector2D::new(4., 30.),
+            font_size: 16,
+            string: String::from(string),
+            color: Color::new(255, 10, 10, 10),
+            clip_point: Vector2D::new(0., 0.),
+            clip_size: Vector2D::new(WIDTH as f64, HEIGHT as f64),
+        }
+    }
+
+    fn collection(pairs: Vec<(usize, RenderInstruction)>) -> RenderInstructionCollection {
+        let mut collection = RenderInstructionCollection::new();
+        for (key, instruction) in pairs {
+            collection.pairs.insert(key, vec![instruction]);
+        }
+        collection
+    }
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> RenderInstruction {
+        RenderInstruction::DrawRect {
+            point: Vector2D::new(x, y),
+            size: Vector2D::new(width, height),
+            color: Color::new(255, 200, 30, 30),
+            clip_point: Vector2D::new(0., 0.),
+            clip_size: Vector2D::new(WIDTH as f64, HEIGHT as f64),
+        }
+    }
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn skips_collections_that_did_not_change() {
+        let mut raqote = Raqote::new(WIDTH, HEIGHT);
+        let frame = collection(vec![(0, rect(10., 10., 20., 20.)), (1, text("Same"))]);
+        assert!(raqote.render_collection(&frame, WIDTH, HEIGHT));
+
+        // Nothing is drawn, not even over the same pixels
+        raqote.dt.clear(SolidSource::from_unpremultiplied_argb(0, 0, 0, 0));
+        assert!(!raqote.render_collection(&frame, WIDTH, HEIGHT));
+        assert!(raqote.dt.get_data().iter().all(|pixel| *pixel == 0));
+
+        let moved = collection(vec![(0, rect(12., 10., 20., 20.)), (1, text("Same"))]);
+        assert!(raqote.render_collection(&moved, WIDTH, HEIGHT));
+    }
+
+    #[test]
+    fn culls_instructions_outside_the_damaged_region() {
+        let pixel = |raqote: &Raqote, x: i32, y: i32| {
+            raqote.dt.get_data()[(y * WIDTH + x) as usize]
+        };
+        let mut raqote = Raqote::new(WIDTH, HEIGHT);
+        raqote.render_collection(
+            &collection(vec![
+                (0, rect(10., 10., 20., 20.)),
+                (1, rect(80., 30., 20., 20.)),
+                (2, rect(500., 500., 20., 20.)),
+            ]),
+            WIDTH,
+            HEIGHT,
+        );
+        assert_eq!((raqote.cull_stats().drawn, raqote.cull_stats().culled), (2, 1));
+
+        // Only the moved rectangle intersects the damage, so the other one
+        // is not painted again over the marked pixel
+        raqote.dt.get_data_mut()[(40 * WIDTH + 90) as usize] = 0;
+        raqote.render_collection(
+            &collection(vec![
+                (0, rect(12., 10., 20., 20.)),
+                (1, rect(80., 30., 20., 20.)),
+                (2, rect(500., 500., 20., 20.)),
+            ]),
+            WIDTH,
+            HEIGHT,
+        );
+        assert_eq!((raqote.cull_stats().drawn, raqote.cull_stats().culled), (1, 2));
+        assert_eq!(pixel(&raqote, 90, 40), 0);
+        assert_ne!(pixel(&raqote, 31, 20), 0);
+        assert_eq!(pixel(&raqote, 85, 35), pixel(&raqote, 31, 20));
+    }
+
+    /// Checks that resizing a draw target of `old` size to `new` size keeps
+    /// the pixels in both sizes in place and fills the rest with the
+    /// background
+    fn check_resize(old: (i32, i32), new: (i32, i32)) {
+        const BACKGROUND: u32 = 0xFF12_3456;
+        let mut raqote = Raqote::new(old.0, old.1);
+        raqote.background = BACKGROUND;
+        for (index, pixel) in raqote.dt.get_data_mut().iter_mut().enumerate() {
+            let (x, y) = (index as u32 % old.0 as u32, index as u32 / old.0 as u32);
+            *pixel = (y << 16) | x;
+        }
+
+        raqote.resize(new.0, new.1);
+        assert_eq!((raqote.dt.width(), raqote.dt.height()), new);
+        assert_eq!(raqote.dt.get_data().len(), (new.0 * new.1) as usize);
+        for (index, pixel) in raqote.dt.get_data().iter().enumerate() {
+            let (x, y) = (index as u32 % new.0 as u32, index as u32 / new.0 as u32);
+            if x < old.0 as u32 && y < old.1 as u32 {
+                assert_eq!(*pixel, (y << 16) | x, "pixel at {}, {}", x, y);
+            } else {
+                assert_eq!(*pixel, BACKGROUND, "pixel at {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_pixels_in_place_when_resizing() {
+        // Growing and shrinking
+        check_resize((10, 8), (17, 12));
+        check_resize((17, 12), (10, 8));
+        // Width and height changing in opposite directions
+        check_resize((10, 40), (25, 6));
+        check_resize((25, 6), (10, 40));
+        // Only one side changing
+        check_resize((10, 8), (10, 20));
+        check_resize((10, 8), (3, 8));
+    }
+
+    #[test]
+    fn pushes_each_clip_once() {
+        let mut raqote = Raqote::new(WIDTH, HEIGHT);
+        let (point, size) = (Vector2D::new(10., 10.), Vector2D::new(20., 20.));
+        raqote.set_clip(&point, &size);
+        raqote.set_clip(&point, &size);
+        raqote.reset_clip();
+
+        // A clip pushed twice would still be there
+        let source = Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 0, 255));
+        raqote.dt.fill_rect(0., 0., WIDTH as f32, HEIGHT as f32, &source, &DrawOptions::new());
+        assert!(raqote.dt.get_data().iter().all(|pixel| *pixel == 0xFF00_00FF));
+    }
+
+    #[test]
+    fn reusing_clips_draws_like_pushing_them_again() {
+        let clip = |x: f64, y: f64, width: f64, height: f64| {
+            let mut instruction = rect(x, y, width, height);
+            if let RenderInstruction::DrawRect {
+                clip_point,
+                clip_size,
+                ..
+            } = &mut instruction
+            {
+                *clip_point = Vector2D::new(10.5, 5.25);
+                *clip_size = Vector2D::new(40.5, 30.75);
+            }
+            instruction
+        };
+        let instructions = [
+            clip(0., 0., 30., 30.),
+            clip(20.25, 15.5, 60., 40.),
+            clip(5., 25., 80., 10.),
+        ];
+        let draw = |reuse: bool, transform: &Transform| {
+            let mut raqote = Raqote::new(WIDTH, HEIGHT);
+            raqote.dt.set_transform(transform);
+            for instruction in instructions.iter() {
+                if !reuse {
+                    raqote.reset_clip();
+                }
+                raqote.draw(instruction);
+            }
+            raqote.reset_clip();
+            raqote.dt.get_data().to_vec()
+        };
+
+        for transform in [
+            Transform::identity(),
+            Transform::create_translation(3.5, -2.25),
+            Transform::create_scale(1.5, 1.5),
+        ]
+        .iter()
+        {
+            assert_eq!(draw(true, transform), draw(false, transform));
+        }
+    }
+
+    #[test]
+    fn redraws_layers_only_when_their_instructions_change() {
+        let layer = |raqote: &Raqote| raqote.layers.get(0).unwrap().dt.get_data().as_ptr();
+        let mut raqote = Raqote::new(WIDTH, HEIGHT);
+        raqote.set_layer_cached(0, true);
+        raqote.render_collection(
+            &collection(vec![(0, rect(10., 10., 20., 20.)), (1, rect(50., 10., 5., 5.))]),
+            WIDTH,
+            HEIGHT,
+        );
+        let first = layer(&raqote);
+
+        // Another key changing keeps the layer
+        raqote.render_collection(
+            &collection(vec![(0, rect(10., 10., 20., 20.)), (1, rect(60., 10., 5., 5.))]),
+            WIDTH,
+            HEIGHT,
+        );
+        assert_eq!(layer(&raqote), first);
+
+        raqote.render_collection(
+            &collection(vec![(0, rect(10., 10., 30., 20.)), (1, rect(60., 10., 5., 5.))]),
+            WIDTH,
+            HEIGHT,
+        );
+        assert_ne!(layer(&raqote), first);
+        assert_eq!(raqote.layers.get(0).unwrap().bounds, Rect::new(9, 9, 41, 31));
+    }
+
+    #[test]
+    fn draws_the_rich_text_of_a_key_instead_of_its_string() {
+        let spans = vec![TextSpan::new("Rich", TextStyle::new(16, Color::new(255, 10, 10, 10)))];
+
+        let mut plain = Raqote::new(WIDTH, HEIGHT);
+        plain.render_collection(&collection(vec![(0, text("Rich"))]), WIDTH, HEIGHT);
+
+        let mut rich = Raqote::new(WIDTH, HEIGHT);
+        let frame = collection(vec![(0, text("Plain"))]);
+        rich.render_collection(&frame, WIDTH, HEIGHT);
+        rich.set_rich_text(0, spans);
+        assert!(rich.render_collection(&frame, WIDTH, HEIGHT));
+        assert_eq!(rich.dt.get_data(), plain.dt.get_data());
+
+        // Going back to the string redraws it
+        rich.remove_rich_text(0);
+        assert!(rich.render_collection(&frame, WIDTH, HEIGHT));
+        assert_ne!(rich.dt.get_data(), plain.dt.get_data());
     }
 }
@@ -0,0 +1,161 @@
+//! Menu bars of the windows of [`DisplayMinifb`](crate::DisplayMinifb)
+//!
+//! A [`MenuTree`] is turned into the native menus of [`minifb`](`crate`).
+//! Activating one of its items, from the menu or its accelerator, queues
+//! [`WindowEvent::MenuActivated`](crate::window::WindowEvent::MenuActivated)
+//! with the item's identifier on
+//! [`DisplayMinifb::window_events`](crate::DisplayMinifb::window_events).
+
+/// Key combination activating a menu item
+#[derive(Clone, Copy, Debug)]
+pub struct Accelerator {
+    pub key: minifb::Key,
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    /// The Windows or Command key
+    pub logo: bool,
+}
+
+impl Accelerator {
+    /// Accelerator of `key` alone
+    pub fn new(key: minifb::Key) -> Self {
+        Accelerator {
+            key,
+            shift: false,
+            control: false,
+            alt: false,
+            logo: false,
+        }
+    }
+
+    /// Accelerator of Ctrl and `key`
+    pub fn control(key: minifb::Key) -> Self {
+        Accelerator {
+            control: true,
+            ..Accelerator::new(key)
+        }
+    }
+
+    /// Gets the modifier flags of [`minifb`](`crate`)
+    fn modifiers(&self) -> usize {
+        let mut modifiers = 0;
+        if self.shift {
+            modifiers |= minifb::MENU_KEY_SHIFT;
+        }
+        if self.control {
+            modifiers |= minifb::MENU_KEY_CTRL;
+        }
+        if self.alt {
+            modifiers |= minifb::MENU_KEY_ALT;
+        }
+        if self.logo {
+            modifiers |= minifb::MENU_KEY_WIN | minifb::MENU_KEY_COMMAND;
+        }
+        modifiers
+    }
+}
+
+/// Entry of a menu
+#[derive(Clone, Debug)]
+pub enum MenuEntry {
+    /// Item sending its `id` when activated
+    Item {
+        label: String,
+        id: usize,
+        accelerator: Option<Accelerator>,
+        enabled: bool,
+    },
+    /// Line between groups of entries
+    Separator,
+    /// Menu opened from the entry
+    Submenu(MenuTree),
+}
+
+/// Menu with its entries, such as File, Edit or View
+#[derive(Clone, Debug)]
+pub struct MenuTree {
+    pub label: String,
+    pub entries: Vec<MenuEntry>,
+}
+
+impl MenuTree {
+    pub fn new(label: &str) -> Self {
+        MenuTree {
+            label: label.to_string(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds an item sending `id` when activated
+    pub fn item(mut self, label: &str, id: usize) -> Self {
+        self.entries.push(MenuEntry::Item {
+            label: label.to_string(),
+            id,
+            accelerator: None,
+            enabled: true,
+        });
+        self
+    }
+
+    /// Adds an item sending `id` when activated, also by `accelerator`
+    pub fn item_with_accelerator(
+        mut self,
+        label: &str,
+        id: usize,
+        accelerator: Accelerator,
+    ) -> Self {
+        self.entries.push(MenuEntry::Item {
+            label: label.to_string(),
+            id,
+            accelerator: Some(accelerator),
+            enabled: true,
+        });
+        self
+    }
+
+    /// Adds an item that is shown but can't be activated
+    pub fn disabled_item(mut self, label: &str, id: usize) -> Self {
+        self.entries.push(MenuEntry::Item {
+            label: label.to_string(),
+            id,
+            accelerator: None,
+            enabled: false,
+        });
+        self
+    }
+
+    pub fn separator(mut self) -> Self {
+        self.entries.push(MenuEntry::Separator);
+        self
+    }
+
+    pub fn submenu(mut self, submenu: MenuTree) -> Self {
+        self.entries.push(MenuEntry::Submenu(submenu));
+        self
+    }
+
+    /// Creates the native menu of the tree
+    pub fn build(&self) -> Result<minifb::Menu, minifb::Error> {
+        let mut menu = minifb::Menu::new(&self.label)?;
+        for entry in self.entries.iter() {
+            match entry {
+                MenuEntry::Item {
+                    label,
+                    id,
+                    accelerator,
+                    enabled,
+                } => {
+                    let mut item = menu.add_item(label, *id).enabled(*enabled);
+                    if let Some(accelerator) = accelerator {
+                        item = item.shortcut(accelerator.key, accelerator.modifiers());
+                    }
+                    item.build();
+                }
+                MenuEntry::Separator => menu.add_separator(),
+                MenuEntry::Submenu(submenu) => menu.add_sub_menu(&submenu.label, &submenu.build()?),
+            }
+        }
+        Ok(menu)
+    }
+}
@@ -0,0 +1,312 @@
+//! Frame pacing and frame time statistics
+
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time left to a deadline below which the pacer stops sleeping and
+/// yields instead, since sleeps may overshoot by the scheduler's granularity
+const SPIN_THRESHOLD: Duration = Duration::from_micros(1500);
+
+/// Longest wait between frames drawn on demand, so that input is still
+/// read promptly at low rates
+pub const MAX_IDLE_WAIT: Duration = Duration::from_millis(100);
+
+/// Weight of the newest frame in the averaged statistics
+const AVERAGE_WEIGHT: f64 = 0.1;
+
+/// Lowest number of frames per second, so that the window's events are
+/// still polled every few seconds
+pub const MIN_FRAME_RATE: f64 = 0.1;
+
+/// Highest number of frames per second
+pub const MAX_FRAME_RATE: f64 = 1000.;
+
+/// How often frames are drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrameRate {
+    /// Frames are drawn as fast as the event loop spins, only limited by
+    /// the update rate of [`minifb`](`crate`)
+    Unlimited,
+    /// Frames are drawn at the given number of frames per second
+    Target(f64),
+    /// Frames are only drawn after input or when the render instructions
+    /// change, at up to `max_rate` frames per second; while idle, the
+    /// window's events are polled `idle_rate` times per second, and at
+    /// least every [`MAX_IDLE_WAIT`]
+    OnDemand { max_rate: f64, idle_rate: f64 },
+}
+
+impl FrameRate {
+    /// Gets the rate with its frames per second clamped between
+    /// [`MIN_FRAME_RATE`] and [`MAX_FRAME_RATE`]
+    ///
+    /// Rates that are not positive finite numbers are rejected.
+    pub fn clamped(self) -> Result<FrameRate, InvalidFrameRate> {
+        let clamp = |rate: f64| {
+            if rate > 0. && rate.is_finite() {
+                Ok(rate.max(MIN_FRAME_RATE).min(MAX_FRAME_RATE))
+            } else {
+                Err(InvalidFrameRate(rate))
+            }
+        };
+        Ok(match self {
+            FrameRate::Unlimited => FrameRate::Unlimited,
+            FrameRate::Target(rate) => FrameRate::Target(clamp(rate)?),
+            FrameRate::OnDemand {
+                max_rate,
+                idle_rate,
+            } => FrameRate::OnDemand {
+                max_rate: clamp(max_rate)?,
+                idle_rate: clamp(idle_rate)?,
+            },
+        })
+    }
+}
+
+/// Frames per second that are not a positive finite number
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InvalidFrameRate(pub f64);
+
+impl fmt::Display for InvalidFrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the frame rate must be a positive number, not {}", self.0)
+    }
+}
+
+impl Error for InvalidFrameRate {}
+
+/// Statistics of the frames handled by a [`FramePacer`]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FrameStats {
+    /// Number of frames rasterized and presented
+    pub presented_frames: u64,
+    /// Number of frames skipped because nothing changed
+    pub skipped_frames: u64,
+    /// Time between the start of the last two frames
+    pub last_frame_time: Duration,
+    /// Moving average of the time between frames
+    pub average_frame_time: Duration,
+    /// Longest time between two frames
+    pub max_frame_time: Duration,
+    /// Time spent drawing and presenting the last presented frame
+    pub last_render_time: Duration,
+    /// Moving average of the time spent drawing and presenting frames
+    pub average_render_time: Duration,
+}
+
+impl FrameStats {
+    /// Frames per second, according to the average frame time
+    pub fn fps(&self) -> f64 {
+        let seconds = self.average_frame_time.as_secs_f64();
+        if seconds > 0. {
+            1. / seconds
+        } else {
+            0.
+        }
+    }
+}
+
+/// Moves `average` toward `sample`
+fn blend(average: Duration, sample: Duration) -> Duration {
+    if average == Duration::default() {
+        return sample;
+    }
+    Duration::from_secs_f64(
+        average.as_secs_f64() * (1. - AVERAGE_WEIGHT) + sample.as_secs_f64() * AVERAGE_WEIGHT,
+    )
+}
+
+/// Sleeps until `deadline`, finishing with short yields for accuracy
+fn sleep_until(deadline: Instant) {
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let remaining = deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            thread::sleep(remaining - SPIN_THRESHOLD);
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
+/// Paces the frames drawn by [`Raqote`](crate::Raqote) and measures them
+pub struct FramePacer {
+    rate: FrameRate,
+    /// When the next frame is due
+    deadline: Option<Instant>,
+    /// When the last frame started
+    last_frame: Option<Instant>,
+    stats: FrameStats,
+}
+
+impl FramePacer {
+    /// Creates a pacer, where invalid rates draw frames without limit
+    pub fn new(rate: FrameRate) -> Self {
+        FramePacer {
+            rate: rate.clamped().unwrap_or(FrameRate::Unlimited),
+            deadline: None,
+            last_frame: None,
+            stats: FrameStats::default(),
+        }
+    }
+
+    pub fn rate(&self) -> FrameRate {
+        self.rate
+    }
+
+    /// Changes the rate, clamped as by [`FrameRate::clamped`], keeping the
+    /// current rate if the new one is invalid
+    pub fn set_rate(&mut self, rate: FrameRate) -> Result<(), InvalidFrameRate> {
+        self.rate = rate.clamped()?;
+        self.deadline = None;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// Clears the statistics gathered so far
+    pub fn reset_stats(&mut self) {
+        self.stats = FrameStats::default();
+        self.last_frame = None;
+    }
+
+    /// Records a frame that started at `started`, and whether it was
+    /// presented or skipped
+    pub fn end_frame(&mut self, started: Instant, presented: bool) {
+        if let Some(last_frame) = self.last_frame {
+            let frame_time = started - last_frame;
+            self.stats.last_frame_time = frame_time;
+            self.stats.average_frame_time = blend(self.stats.average_frame_time, frame_time);
+            self.stats.max_frame_time = self.stats.max_frame_time.max(frame_time);
+        }
+        self.last_frame = Some(started);
+
+        if presented {
+            let render_time = started.elapsed();
+            self.stats.presented_frames += 1;
+            self.stats.last_render_time = render_time;
+            self.stats.average_render_time = blend(self.stats.average_render_time, render_time);
+        } else {
+            self.stats.skipped_frames += 1;
+        }
+    }
+
+    /// Waits until the next frame is due, where `active` tells whether
+    /// the last frame was presented or followed input
+    pub fn wait(&mut self, active: bool) {
+        if let Some(deadline) = self.next_deadline(Instant::now(), active) {
+            sleep_until(deadline);
+            self.deadline = Some(deadline);
+        }
+    }
+
+    /// When the frame after one ending at `now` is due, or `None` if it
+    /// is due right away
+    fn next_deadline(&self, now: Instant, active: bool) -> Option<Instant> {
+        let (rate, max_wait) = match self.rate {
+            FrameRate::Unlimited => return None,
+            FrameRate::Target(rate) => (rate, None),
+            FrameRate::OnDemand { max_rate, .. } if active => (max_rate, Some(MAX_IDLE_WAIT)),
+            FrameRate::OnDemand { idle_rate, .. } => (idle_rate, Some(MAX_IDLE_WAIT)),
+        };
+
+        let period = Duration::from_secs_f64(1. / rate);
+        let deadline = match self.deadline {
+            // Keeps a steady cadence, unless the frame is already late
+            Some(deadline) if deadline + period > now => deadline + period,
+            _ => now + period,
+        };
+        Some(match max_wait {
+            Some(max_wait) => deadline.min(now + max_wait),
+            None => deadline,
+        })
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        FramePacer::new(FrameRate::Unlimited)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_rates_that_are_not_positive_numbers() {
+        for rate in [0., -30., f64::NAN, f64::INFINITY].iter() {
+            assert!(FrameRate::Target(*rate).clamped().is_err());
+        }
+        let on_demand = FrameRate::OnDemand {
+            max_rate: 60.,
+            idle_rate: f64::NAN,
+        };
+        assert!(on_demand.clamped().is_err());
+    }
+
+    #[test]
+    fn clamps_extreme_rates() {
+        assert_eq!(FrameRate::Target(1e-300).clamped(), Ok(FrameRate::Target(MIN_FRAME_RATE)));
+        assert_eq!(FrameRate::Target(1e9).clamped(), Ok(FrameRate::Target(MAX_FRAME_RATE)));
+        assert_eq!(FrameRate::Target(60.).clamped(), Ok(FrameRate::Target(60.)));
+    }
+
+    #[test]
+    fn paces_frames_at_the_target_rate() {
+        let now = Instant::now();
+        let period = Duration::from_millis(20);
+        let mut pacer = FramePacer::new(FrameRate::Target(50.));
+        assert_eq!(pacer.next_deadline(now, true), Some(now + period));
+
+        // Frames finishing early keep the cadence of the previous deadline
+        pacer.deadline = Some(now + period);
+        let early = now + Duration::from_millis(25);
+        assert_eq!(pacer.next_deadline(early, false), Some(now + period * 2));
+        // Late frames start a new cadence
+        let late = now + Duration::from_millis(45);
+        assert_eq!(pacer.next_deadline(late, false), Some(late + period));
+    }
+
+    #[test]
+    fn never_waits_without_a_limit() {
+        let pacer = FramePacer {
+            deadline: Some(Instant::now()),
+            ..FramePacer::default()
+        };
+        assert_eq!(pacer.next_deadline(Instant::now(), true), None);
+    }
+
+    #[test]
+    fn polls_idle_windows_at_the_idle_rate() {
+        let now = Instant::now();
+        let pacer = FramePacer::new(FrameRate::OnDemand {
+            max_rate: 100.,
+            idle_rate: 20.,
+        });
+        assert_eq!(pacer.next_deadline(now, true), Some(now + Duration::from_millis(10)));
+        assert_eq!(pacer.next_deadline(now, false), Some(now + Duration::from_millis(50)));
+
+        // Low idle rates still read input every so often
+        let slow = FramePacer::new(FrameRate::OnDemand {
+            max_rate: 60.,
+            idle_rate: MIN_FRAME_RATE,
+        });
+        assert_eq!(slow.next_deadline(now, false), Some(now + MAX_IDLE_WAIT));
+    }
+
+    #[test]
+    fn keeps_the_rate_when_rejecting_one() {
+        let mut pacer = FramePacer::new(FrameRate::Target(30.));
+        assert!(pacer.set_rate(FrameRate::Target(f64::NAN)).is_err());
+        assert_eq!(pacer.rate(), FrameRate::Target(30.));
+        assert_eq!(FramePacer::new(FrameRate::Target(-1.)).rate(), FrameRate::Unlimited);
+    }
+}
@@ -0,0 +1,182 @@
+//! Conversions between the premultiplied ARGB pixels of [`raqote`](`crate`)
+//! and the pixels decoded by [`image`](`crate`) or presented by
+//! [`minifb`](`crate`)
+//!
+//! The conversions go over whole slices with branchless integer math, so
+//! the compiler can vectorize them.
+
+use image::{DynamicImage, RgbaImage};
+
+/// Multiplies two 8 bit channels, rounding as if divided by 255
+#[inline(always)]
+fn mul_255(c: u32, a: u32) -> u32 {
+    let t = c * a + 128;
+    (t + (t >> 8)) >> 8
+}
+
+/// Multiplies two 16 bit channels, rounding as if divided by 65535
+#[inline(always)]
+fn mul_65535(c: u32, a: u32) -> u32 {
+    let t = c as u64 * a as u64 + 32768;
+    ((t + (t >> 16)) >> 16) as u32
+}
+
+/// Narrows a 16 bit channel to 8 bits, rounding to the nearest value
+#[inline(always)]
+fn narrow(c: u32) -> u32 {
+    (c * 255 + 32895) >> 16
+}
+
+/// Packs 8 bit channels into a premultiplied ARGB pixel
+#[inline(always)]
+fn premultiply(a: u32, r: u32, g: u32, b: u32) -> u32 {
+    (a << 24) | (mul_255(r, a) << 16) | (mul_255(g, a) << 8) | mul_255(b, a)
+}
+
+/// Packs opaque 8 bit channels into an ARGB pixel
+#[inline(always)]
+fn opaque(r: u32, g: u32, b: u32) -> u32 {
+    0xff00_0000 | (r << 16) | (g << 8) | b
+}
+
+/// Packs 16 bit channels into a premultiplied ARGB pixel, premultiplying
+/// before narrowing them
+#[inline(always)]
+fn premultiply_16(a: u32, r: u32, g: u32, b: u32) -> u32 {
+    (narrow(a) << 24)
+        | (narrow(mul_65535(r, a)) << 16)
+        | (narrow(mul_65535(g, a)) << 8)
+        | narrow(mul_65535(b, a))
+}
+
+/// Converts the pixels of `channels` samples in `samples` with `pixel`
+#[inline(always)]
+fn convert<T: Copy>(samples: &[T], channels: usize, pixel: impl Fn(&[T]) -> u32) -> Vec<u32> {
+    samples.chunks_exact(channels).map(pixel).collect()
+}
+
+/// Converts RGBA pixels, with straight alpha, to premultiplied ARGB
+pub fn rgba_to_argb(rgba: &[u8]) -> Vec<u32> {
+    convert(rgba, 4, |p| {
+        premultiply(p[3] as u32, p[0] as u32, p[1] as u32, p[2] as u32)
+    })
+}
+
+/// Converts the pixels of a decoded image to premultiplied ARGB, whatever
+/// its color type
+pub fn image_to_argb(image: &DynamicImage) -> Vec<u32> {
+    match image {
+        DynamicImage::ImageLuma8(image) => convert(image.as_raw(), 1, |p| {
+            let l = p[0] as u32;
+            opaque(l, l, l)
+        }),
+        DynamicImage::ImageLumaA8(image) => convert(image.as_raw(), 2, |p| {
+            let l = p[0] as u32;
+            premultiply(p[1] as u32, l, l, l)
+        }),
+        DynamicImage::ImageRgb8(image) => convert(image.as_raw(), 3, |p| {
+            opaque(p[0] as u32, p[1] as u32, p[2] as u32)
+        }),
+        DynamicImage::ImageRgba8(image) => rgba_to_argb(image.as_raw()),
+        DynamicImage::ImageBgr8(image) => convert(image.as_raw(), 3, |p| {
+            opaque(p[2] as u32, p[1] as u32, p[0] as u32)
+        }),
+        DynamicImage::ImageBgra8(image) => convert(image.as_raw(), 4, |p| {
+            premultiply(p[3] as u32, p[2] as u32, p[1] as u32, p[0] as u32)
+        }),
+        DynamicImage::ImageLuma16(image) => convert(image.as_raw(), 1, |p| {
+            let l = narrow(p[0] as u32);
+            opaque(l, l, l)
+        }),
+        DynamicImage::ImageLumaA16(image) => convert(image.as_raw(), 2, |p| {
+            let l = p[0] as u32;
+            premultiply_16(p[1] as u32, l, l, l)
+        }),
+        DynamicImage::ImageRgb16(image) => convert(image.as_raw(), 3, |p| {
+            opaque(narrow(p[0] as u32), narrow(p[1] as u32), narrow(p[2] as u32))
+        }),
+        DynamicImage::ImageRgba16(image) => convert(image.as_raw(), 4, |p| {
+            premultiply_16(p[3] as u32, p[0] as u32, p[1] as u32, p[2] as u32)
+        }),
+    }
+}
+
+/// Gets the factors undoing the premultiplication of each alpha, in 16.16
+/// fixed point
+fn unpremultiply_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    for (a, factor) in table.iter_mut().enumerate().skip(1) {
+        *factor = ((255 << 16) + a as u32 / 2) / a as u32;
+    }
+    table
+}
+
+/// Converts premultiplied ARGB pixels to ARGB with straight alpha, into
+/// `straight`, whose allocation is reused
+pub fn unpremultiply_argb(argb: &[u32], straight: &mut Vec<u32>) {
+    let table = unpremultiply_table();
+    straight.clear();
+    straight.extend(argb.iter().map(|pixel| {
+        let a = pixel >> 24;
+        let factor = table[a as usize];
+        let unpremultiply = |c: u32| ((c * factor + 32768) >> 16).min(255);
+        (a << 24)
+            | (unpremultiply((pixel >> 16) & 0xff) << 16)
+            | (unpremultiply((pixel >> 8) & 0xff) << 8)
+            | unpremultiply(pixel & 0xff)
+    }));
+}
+
+/// Converts premultiplied ARGB pixels to RGBA, with straight alpha
+pub fn argb_to_rgba(argb: &[u32]) -> Vec<u8> {
+    let table = unpremultiply_table();
+    let mut rgba = vec![0; argb.len() * 4];
+    for (pixel, out) in argb.iter().zip(rgba.chunks_exact_mut(4)) {
+        let a = pixel >> 24;
+        let factor = table[a as usize];
+        let unpremultiply = |c: u32| ((c * factor + 32768) >> 16).min(255) as u8;
+        out[0] = unpremultiply((pixel >> 16) & 0xff);
+        out[1] = unpremultiply((pixel >> 8) & 0xff);
+        out[2] = unpremultiply(pixel & 0xff);
+        out[3] = a as u8;
+    }
+    rgba
+}
+
+/// Converts premultiplied ARGB pixels to an image of `width` by `height`
+/// pixels, to be encoded by [`image`](`crate`)
+pub fn argb_to_image(argb: &[u32], width: u32, height: u32) -> Option<RgbaImage> {
+    RgbaImage::from_raw(width, height, argb_to_rgba(argb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiplies_translucent_pixels() {
+        let argb = rgba_to_argb(&[255, 128, 0, 128, 10, 20, 30, 0]);
+        assert_eq!(argb, vec![0x8080_4000, 0]);
+    }
+
+    #[test]
+    fn round_trips_opaque_pixels() {
+        let rgba: Vec<u8> = (0..=255).flat_map(|c| vec![c, 255 - c, c / 2, 255]).collect();
+        assert_eq!(argb_to_rgba(&rgba_to_argb(&rgba)), rgba);
+    }
+
+    #[test]
+    fn unpremultiplies_translucent_pixels() {
+        let mut straight = Vec::new();
+        unpremultiply_argb(&[0x8080_4000, 0], &mut straight);
+        assert_eq!(straight, vec![0x80ff_8000, 0]);
+    }
+
+    #[test]
+    fn narrows_16_bit_images() {
+        let image = image::ImageBuffer::from_raw(2, 1, vec![65535u16, 65535, 0, 32896])
+            .map(DynamicImage::ImageLumaA16)
+            .unwrap();
+        assert_eq!(image_to_argb(&image), vec![0xffff_ffff, 0x8000_0000]);
+    }
+}
@@ -0,0 +1,524 @@
+//! Text styling and layout on top of [`font_kit`](`crate`)
+//!
+//! Styled spans of text are resolved to fonts and laid out as glyphs
+//! sitting on shared baselines, ready to be rendered by [`Raqote`].
+//!
+//! [`Raqote`]: crate::Raqote
+
+use font_kit::family_name::FamilyName;
+use font_kit::font::Font;
+use font_kit::metrics::Metrics;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+
+use hyber::util::{Color, Vector2D};
+
+use raqote::Point;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Style of a [`TextSpan`]
+#[derive(Clone)]
+pub struct TextStyle {
+    /// Family of the font used to render the text
+    pub family: FamilyName,
+    /// Weight of the font, from 100 (thin) to 900 (black)
+    pub weight: f32,
+    /// Whether the italic style of the font is used
+    pub italic: bool,
+    /// Size of the font in pixels
+    pub font_size: usize,
+    /// Color of the text and its decorations
+    pub color: Color,
+    /// Whether a line is drawn under the text
+    pub underline: bool,
+    /// Whether a line is drawn through the text
+    pub strikethrough: bool,
+}
+
+impl TextStyle {
+    /// Regular sans-serif style without decorations
+    pub fn new(font_size: usize, color: Color) -> Self {
+        TextStyle {
+            family: FamilyName::SansSerif,
+            weight: Weight::NORMAL.0,
+            italic: false,
+            font_size,
+            color,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+}
+
+/// Piece of text rendered with a single [`TextStyle`]
+#[derive(Clone)]
+pub struct TextSpan {
+    /// The text of the span, which may contain line breaks
+    pub text: String,
+    /// The style of the whole span
+    pub style: TextStyle,
+}
+
+impl TextSpan {
+    pub fn new(text: &str, style: TextStyle) -> Self {
+        TextSpan {
+            text: String::from(text),
+            style,
+        }
+    }
+
+    /// Gets the span with its font size multiplied by `scale`
+    pub fn scaled(&self, scale: f64) -> TextSpan {
+        let mut span = self.clone();
+        span.style.font_size = (self.style.font_size as f64 * scale).round() as usize;
+        span
+    }
+}
+
+/// Families looked up for the characters missing from a span's font,
+/// such as emoji
+const FALLBACK_FAMILIES: [&str; 5] = [
+    "Noto Color Emoji",
+    "Apple Color Emoji",
+    "Segoe UI Emoji",
+    "Twemoji Mozilla",
+    "EmojiOne Color",
+];
+
+/// Key identifying a font loaded by the [`FontCache`]
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct FontKey {
+    family: String,
+    weight: u32,
+    italic: bool,
+}
+
+impl FontKey {
+    fn new(style: &TextStyle) -> Self {
+        let family = match &style.family {
+            FamilyName::Title(name) => name.clone(),
+            FamilyName::Serif => String::from("serif"),
+            FamilyName::SansSerif => String::from("sans-serif"),
+            FamilyName::Monospace => String::from("monospace"),
+            FamilyName::Cursive => String::from("cursive"),
+            FamilyName::Fantasy => String::from("fantasy"),
+        };
+        FontKey {
+            family,
+            weight: style.weight as u32,
+            italic: style.italic,
+        }
+    }
+}
+
+/// Fonts loaded from the system, so that they are only looked up once
+///
+/// Each loaded font gets an identifier that stays the same for the
+/// lifetime of the cache.
+#[derive(Default)]
+pub struct FontCache {
+    ids: HashMap<FontKey, usize>,
+    fonts: Vec<Rc<Font>>,
+    /// Identifiers of the fonts of [`FALLBACK_FAMILIES`] found on the system,
+    /// once they were looked up
+    fallbacks: Option<Vec<usize>>,
+}
+
+impl FontCache {
+    pub fn new() -> Self {
+        FontCache::default()
+    }
+
+    /// Gets the identifier and the font that best matches the family,
+    /// weight and style of `style`, falling back to a sans-serif font
+    pub fn get(&mut self, style: &TextStyle) -> (usize, Rc<Font>) {
+        let fonts = &mut self.fonts;
+        let id = *self.ids.entry(FontKey::new(style)).or_insert_with(|| {
+            let mut properties = Properties::new();
+            properties.weight(Weight(style.weight));
+            if style.italic {
+                properties.style(Style::Italic);
+            }
+            let font = SystemSource::new()
+                .select_best_match(&[style.family.clone(), FamilyName::SansSerif], &properties)
+                .unwrap()
+                .load()
+                .unwrap();
+            fonts.push(Rc::new(font));
+            fonts.len() - 1
+        });
+        (id, self.fonts[id].clone())
+    }
+
+    /// Gets the identifier and the first fallback font that has a glyph
+    /// for `character`
+    pub fn fallback(&mut self, character: char) -> Option<(usize, Rc<Font>)> {
+        if self.fallbacks.is_none() {
+            let mut fallbacks = Vec::new();
+            for family in FALLBACK_FAMILIES.iter() {
+                let font = SystemSource::new()
+                    .select_best_match(&[FamilyName::Title(family.to_string())], &Properties::new())
+                    .ok()
+                    .and_then(|handle| handle.load().ok());
+                if let Some(font) = font {
+                    self.fonts.push(Rc::new(font));
+                    fallbacks.push(self.fonts.len() - 1);
+                }
+            }
+            self.fallbacks = Some(fallbacks);
+        }
+        let fonts = &self.fonts;
+        self.fallbacks
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|id| fonts[**id].glyph_for_char(character).is_some())
+            .map(|id| (*id, fonts[*id].clone()))
+    }
+}
+
+/// Glyph placed by a [`TextLayout`]
+#[derive(Clone, Copy)]
+pub struct PositionedGlyph {
+    /// Identifier of the glyph within its font
+    pub id: u32,
+    /// Position of the glyph's origin, on the line's baseline
+    pub position: Point,
+    /// Horizontal advance of the glyph
+    pub advance: f32,
+    /// Byte offset, within [`TextLayout::text`], of the character
+    /// that produced the glyph
+    pub cluster: usize,
+}
+
+/// Consecutive glyphs of a line sharing the same style
+pub struct GlyphRun {
+    /// Identifier of the font within its [`FontCache`]
+    pub font_id: usize,
+    /// The font the glyphs belong to
+    pub font: Rc<Font>,
+    /// The style of the span the glyphs come from
+    pub style: TextStyle,
+    /// The glyphs, in visual order
+    pub glyphs: Vec<PositionedGlyph>,
+}
+
+impl GlyphRun {
+    /// Horizontal start and width covered by the run
+    pub fn extent(&self) -> (f32, f32) {
+        match (self.glyphs.first(), self.glyphs.last()) {
+            (Some(first), Some(last)) => (
+                first.position.x,
+                last.position.x + last.advance - first.position.x,
+            ),
+            _ => (0., 0.),
+        }
+    }
+}
+
+/// Line of text whose runs share the same baseline
+pub struct TextLine {
+    /// Vertical position of the baseline
+    pub baseline: f32,
+    /// Largest ascent of the fonts used in the line
+    pub ascent: f32,
+    /// Largest descent of the fonts used in the line
+    pub descent: f32,
+    /// Largest line gap of the fonts used in the line
+    pub line_gap: f32,
+    /// Horizontal position where the line starts
+    pub x: f32,
+    /// Width of the line
+    pub width: f32,
+    /// Byte range of [`TextLayout::text`] covered by the line,
+    /// without its line break
+    pub range: Range<usize>,
+    /// The runs of glyphs of the line
+    pub runs: Vec<GlyphRun>,
+}
+
+/// Spans of styled text laid out on shared baselines
+pub struct TextLayout {
+    /// Concatenated text of all the spans
+    pub text: String,
+    /// The lines of the text, from top to bottom
+    pub lines: Vec<TextLine>,
+}
+
+impl TextLayout {
+    /// Lays out `spans` so that the first baseline starts at `origin`,
+    /// breaking lines on `'\n'`
+    pub fn new(fonts: &mut FontCache, origin: (f32, f32), spans: &[TextSpan]) -> Self {
+        let mut text = String::new();
+        let mut lines = Vec::new();
+        let mut line = TextLine::empty(origin.0, 0);
+
+        for span in spans {
+            let (font_id, font) = fonts.get(&span.style);
+            let metrics = font.metrics();
+            let scale = span.style.font_size as f32 / metrics.units_per_em as f32;
+
+            for (index, segment) in span.text.split('\n').enumerate() {
+                if index > 0 {
+                    line.range.end = text.len();
+                    text.push('\n');
+                    lines.push(line);
+                    line = TextLine::empty(origin.0, text.len());
+                }
+
+                line.include(&metrics, scale);
+
+                // Characters missing from the span's font are taken from a
+                // fallback font, in a run of their own
+                let mut pen_x = line.x + line.width;
+                let mut run: Option<GlyphRun> = None;
+                for (offset, character) in segment.char_indices() {
+                    let (glyph_font_id, glyph_font, id, glyph_scale) =
+                        match font.glyph_for_char(character) {
+                            Some(id) => (font_id, font.clone(), id, scale),
+                            None => match fonts.fallback(character) {
+                                Some((fallback_id, fallback)) => {
+                                    let fallback_metrics = fallback.metrics();
+                                    let fallback_scale = span.style.font_size as f32
+                                        / fallback_metrics.units_per_em as f32;
+                                    line.include(&fallback_metrics, fallback_scale);
+                                    let id = fallback.glyph_for_char(character).unwrap_or(0);
+                                    (fallback_id, fallback, id, fallback_scale)
+                                }
+                                None => (font_id, font.clone(), 0, scale),
+                            },
+                        };
+
+                    if run.as_ref().map_or(true, |run| run.font_id != glyph_font_id) {
+                        if let Some(run) = run.take() {
+                            line.runs.push(run);
+                        }
+                        run = Some(GlyphRun {
+                            font_id: glyph_font_id,
+                            font: glyph_font.clone(),
+                            style: span.style.clone(),
+                            glyphs: Vec::new(),
+                        });
+                    }
+
+                    let advance = glyph_font
+                        .advance(id)
+                        .map(|a| a.x() * glyph_scale)
+                        .unwrap_or(0.);
+                    run.as_mut().unwrap().glyphs.push(PositionedGlyph {
+                        id,
+                        position: Point::new(pen_x, 0.),
+                        advance,
+                        cluster: text.len() + offset,
+                    });
+                    pen_x += advance;
+                }
+                text.push_str(segment);
+
+                line.width = pen_x - line.x;
+                if let Some(run) = run {
+                    line.runs.push(run);
+                }
+            }
+        }
+        line.range.end = text.len();
+        lines.push(line);
+
+        // Stacks the lines, keeping the first baseline at the origin
+        let mut baseline = origin.1;
+        for index in 0..lines.len() {
+            if index > 0 {
+                let previous = &lines[index - 1];
+                baseline += previous.descent + previous.line_gap + lines[index].ascent;
+            }
+            let line = &mut lines[index];
+            line.baseline = baseline;
+            for run in line.runs.iter_mut() {
+                for glyph in run.glyphs.iter_mut() {
+                    glyph.position.y = baseline;
+                }
+            }
+        }
+
+        TextLayout { text, lines }
+    }
+}
+
+impl TextLayout {
+    /// Byte offsets of every grapheme boundary of the text, including
+    /// the end of the text, so that grapheme `i` starts at offset `i`
+    fn grapheme_offsets(&self) -> Vec<usize> {
+        let mut offsets: Vec<usize> = self
+            .text
+            .grapheme_indices(true)
+            .map(|(offset, _)| offset)
+            .collect();
+        offsets.push(self.text.len());
+        offsets
+    }
+
+    /// Number of graphemes of the text
+    pub fn grapheme_count(&self) -> usize {
+        self.text.graphemes(true).count()
+    }
+
+    /// Gets the line that holds the caret for the byte `offset`
+    fn line_for_offset(&self, offset: usize) -> &TextLine {
+        self.lines
+            .iter()
+            .find(|line| offset <= line.range.end)
+            .unwrap_or_else(|| self.lines.last().unwrap())
+    }
+
+    /// Gets the grapheme index closest to the point (`x`, `y`), that is,
+    /// where a caret should be placed when that point is clicked
+    pub fn hit_test(&self, x: f32, y: f32) -> usize {
+        let line = self
+            .lines
+            .iter()
+            .find(|line| y < line.baseline + line.descent + line.line_gap)
+            .unwrap_or_else(|| self.lines.last().unwrap());
+
+        let mut closest = (0, f32::INFINITY);
+        for (index, offset) in self.grapheme_offsets().into_iter().enumerate() {
+            if offset < line.range.start || offset > line.range.end {
+                continue;
+            }
+            let distance = (line.x_at(offset) - x).abs();
+            if distance < closest.1 {
+                closest = (index, distance);
+            }
+        }
+        closest.0
+    }
+
+    /// Gets the rectangle, as its point and size, of the caret placed
+    /// before the grapheme `index`
+    pub fn caret_rect(&self, index: usize) -> (Vector2D, Vector2D) {
+        let offsets = self.grapheme_offsets();
+        let offset = offsets[index.min(offsets.len() - 1)];
+        let line = self.line_for_offset(offset);
+        (
+            Vector2D::new(line.x_at(offset) as f64, (line.baseline - line.ascent) as f64),
+            Vector2D::new(1., (line.ascent + line.descent) as f64),
+        )
+    }
+
+    /// Gets the rectangles, as their points and sizes, covering the
+    /// graphemes within `range`, one for each line it spans
+    ///
+    /// The range may be reversed, as when a selection is dragged backwards.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<(Vector2D, Vector2D)> {
+        let offsets = self.grapheme_offsets();
+        let last = offsets.len() - 1;
+        let start = offsets[range.start.min(range.end).min(last)];
+        let end = offsets[range.start.max(range.end).min(last)];
+
+        let mut rects = Vec::new();
+        for line in self.lines.iter() {
+            if end < line.range.start || start > line.range.end || start == end {
+                continue;
+            }
+            let x0 = line.x_at(start.max(line.range.start));
+            let x1 = line.x_at(end.min(line.range.end));
+            rects.push((
+                Vector2D::new(x0 as f64, (line.baseline - line.ascent) as f64),
+                Vector2D::new((x1 - x0) as f64, (line.ascent + line.descent) as f64),
+            ));
+        }
+        rects
+    }
+}
+
+impl TextLine {
+    /// Grows the line's metrics to fit the metrics of a font scaled by `scale`
+    fn include(&mut self, metrics: &Metrics, scale: f32) {
+        self.ascent = self.ascent.max(metrics.ascent * scale);
+        self.descent = self.descent.max(-metrics.descent * scale);
+        self.line_gap = self.line_gap.max(metrics.line_gap * scale);
+    }
+
+    /// Horizontal position of the caret placed before the character at
+    /// the byte `offset`
+    fn x_at(&self, offset: usize) -> f32 {
+        self.runs
+            .iter()
+            .flat_map(|run| run.glyphs.iter())
+            .find(|glyph| glyph.cluster >= offset)
+            .map(|glyph| glyph.position.x)
+            .unwrap_or(self.x + self.width)
+    }
+
+    fn empty(x: f32, start: usize) -> Self {
+        TextLine {
+            baseline: 0.,
+            ascent: 0.,
+            descent: 0.,
+            line_gap: 0.,
+            x,
+            width: 0.,
+            range: start..start,
+            runs: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(text: &str) -> TextLayout {
+        let spans = [TextSpan::new(text, TextStyle::new(16, Color::new(255, 0, 0, 0)))];
+        TextLayout::new(&mut FontCache::new(), (10., 20.), &spans)
+    }
+
+    #[test]
+    fn hit_tests_the_caret_positions() {
+        let layout = layout("hello\nworld");
+        for index in 0..=layout.grapheme_count() {
+            let (point, size) = layout.caret_rect(index);
+            let y = (point.y + size.y / 2.) as f32;
+            assert_eq!(layout.hit_test(point.x as f32, y), index);
+        }
+    }
+
+    #[test]
+    fn places_carets_on_their_line() {
+        let layout = layout("ab\ncd");
+        let (first, _) = layout.caret_rect(1);
+        let (second, _) = layout.caret_rect(4);
+        assert_eq!(first.x, 10. + layout.lines[0].runs[0].glyphs[0].advance as f64);
+        assert!(second.y > first.y);
+        // Indices past the end place the caret at the end of the text
+        let (end, _) = layout.caret_rect(5);
+        assert_eq!(layout.caret_rect(100).0.x, end.x);
+    }
+
+    #[test]
+    fn selects_one_rect_per_line() {
+        let layout = layout("ab\ncd");
+        let rects = layout.selection_rects(1..4);
+        assert_eq!(rects.len(), 2);
+        assert!(rects.iter().all(|(_, size)| size.x > 0.));
+        assert!(layout.selection_rects(2..2).is_empty());
+    }
+
+    #[test]
+    fn selects_reversed_ranges() {
+        let layout = layout("hello\nworld");
+        let forward = layout.selection_rects(2..8);
+        let backward = layout.selection_rects(8..2);
+        assert_eq!(forward.len(), backward.len());
+        for ((point_a, size_a), (point_b, size_b)) in forward.iter().zip(backward.iter()) {
+            assert_eq!((point_a.x, point_a.y), (point_b.x, point_b.y));
+            assert_eq!((size_a.x, size_a.y), (size_b.x, size_b.y));
+            assert!(size_a.x >= 0.);
+        }
+        assert_eq!(layout.selection_rects(100..3).len(), 2);
+    }
+}
@@ -0,0 +1,231 @@
+//! Multithreaded tiled rasterization
+//!
+//! The region to paint is split into tiles of whole rows, and each tile is
+//! drawn on a [`rayon`](`crate`) worker thread with only the instructions
+//! whose bounds touch it.
+//!
+//! Every worker keeps a [`Raqote`] as large as the output, so that the
+//! instructions are rasterized at the same coordinates as on a single
+//! thread and only clipped to the tile. The output is therefore identical
+//! to the single-threaded one, at the cost of a draw target per worker.
+
+use crate::geometry::Rect;
+use crate::glyph_cache::TextRenderOptions;
+use crate::layers::DrawItem;
+use crate::Raqote;
+
+use raqote::DrawTarget;
+
+use rayon::prelude::*;
+
+use std::cell::RefCell;
+
+/// Default number of rows of a tile
+pub const DEFAULT_TILE_HEIGHT: i32 = 64;
+
+thread_local! {
+    /// Renderer of the tiles drawn by the current worker thread
+    static PAINTER: RefCell<Option<Raqote>> = RefCell::new(None);
+}
+
+/// Options of the multithreaded tiled rasterization
+#[derive(Clone, Copy, Debug)]
+pub struct TiledRendering {
+    /// Number of rows of each tile
+    pub tile_height: i32,
+}
+
+impl Default for TiledRendering {
+    fn default() -> Self {
+        TiledRendering {
+            tile_height: DEFAULT_TILE_HEIGHT,
+        }
+    }
+}
+
+/// Paints again the pixels of `dt` within `region`, drawing the tiles in
+/// parallel as a [`Raqote`] of scale factor `scale` would
+pub fn draw_tiled(
+    dt: &mut DrawTarget,
+    region: Rect,
+    items: &[(DrawItem, Option<Rect>)],
+    tiled: TiledRendering,
+    text_options: TextRenderOptions,
+    scale: f64,
+) {
+    let width = dt.width();
+    let height = dt.height();
+    let tile_height = tiled.tile_height.max(1);
+    if width <= 0 || height <= 0 || region.is_empty() {
+        return;
+    }
+
+    dt.get_data_mut()
+        .par_chunks_mut((width * tile_height) as usize)
+        .enumerate()
+        .for_each(|(index, pixels)| {
+            let top = index as i32 * tile_height;
+            let rows = pixels.len() as i32 / width;
+            let tile = Rect::new(0, top, width, top + rows).intersection(&region);
+            if tile.is_empty() {
+                return;
+            }
+
+            // Bins the instructions and layers painting within the tile
+            let binned: Vec<(DrawItem, Option<Rect>)> = items
+                .iter()
+                .filter(|(_, bounds)| bounds.map_or(true, |bounds| bounds.intersects(&tile)))
+                .copied()
+                .collect();
+
+            PAINTER.with(|painter| {
+                let mut painter = painter.borrow_mut();
+                let painter = painter.get_or_insert_with(|| Raqote::new(width, height));
+                if painter.dt.width() != width || painter.dt.height() != height {
+                    painter.dt = DrawTarget::new(width, height);
+                }
+                // The options come from a renderer, which only holds valid ones
+                let _ = painter.set_text_render_options(text_options);
+                painter.set_scale(scale);
+
+                // Starts from the current pixels of the tile, since the
+                // instructions may not paint all of them
+                let start = (top * width) as usize;
+                let end = start + pixels.len();
+                painter.dt.get_data_mut()[start..end].copy_from_slice(pixels);
+                painter.draw_region(tile, &binned);
+                pixels.copy_from_slice(&painter.dt.get_data()[start..end]);
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::text::{TextSpan, TextStyle};
+
+    use hyber::renderer::{DrawImageOptions, RenderInstruction, RenderInstructionCollection};
+    use hyber::util::{Color, Vector2D};
+
+    const WIDTH: i32 = 200;
+    const HEIGHT: i32 = 150;
+
+    fn point(x: f64, y: f64) -> Vector2D {
+        Vector2D::new(x, y)
+    }
+
+    /// Shapes, text and images crossing the edges of tiles of 16 rows,
+    /// where the circle moves by `offset`
+    fn scene(offset: f64) -> RenderInstructionCollection {
+        let color = Color::new(200, 30, 120, 220);
+        let mut collection = RenderInstructionCollection::new();
+        collection.pairs.insert(
+            0,
+            vec![RenderInstruction::Clear {
+                color: Color::new(255, 255, 255, 255),
+            }],
+        );
+        collection.pairs.insert(
+            1,
+            vec![
+                RenderInstruction::DrawCircle {
+                    point: point(60.3 + offset, 47.6),
+                    r: 30,
+                    color: color.clone(),
+                    clip_point: point(0., 0.),
+                    clip_size: point(WIDTH as f64, HEIGHT as f64),
+                },
+                RenderInstruction::DrawLine {
+                    point_a: point(5.5, 140.2),
+                    point_b: point(190.7, 10.1),
+                    color: color.clone(),
+                    clip_point: point(0., 0.),
+                    clip_size: point(WIDTH as f64, HEIGHT as f64),
+                },
+                RenderInstruction::DrawTriangle {
+                    point_a: point(120.2, 15.5),
+                    point_b: point(195.9, 95.3),
+                    point_c: point(101.1, 90.7),
+                    color: color.clone(),
+                    clip_point: point(0., 0.),
+                    clip_size: point(WIDTH as f64, HEIGHT as f64),
+                },
+            ],
+        );
+        collection.pairs.insert(
+            2,
+            vec![RenderInstruction::DrawText {
+                point: point(8.25, 31.5),
+                font_size: 20,
+                string: String::from("Tiles glyphs"),
+                color: Color::new(255, 10, 10, 10),
+                clip_point: point(0., 0.),
+                clip_size: point(WIDTH as f64, HEIGHT as f64),
+            }],
+        );
+        // The clip cuts through the image and through a tile
+        collection.pairs.insert(
+            3,
+            vec![RenderInstruction::DrawImage {
+                point: point(90., 60.),
+                path: String::from("rust.png"),
+                options: DrawImageOptions::Resize {
+                    width: 80,
+                    height: 80,
+                },
+                clip_point: point(100.5, 70.5),
+                clip_size: point(50., 29.),
+            }],
+        );
+        collection
+    }
+
+    fn assert_same_pixels(single: &Raqote, tiled: &Raqote) {
+        let width = single.dt.width();
+        let single = single.dt.get_data();
+        let tiled = tiled.dt.get_data();
+        assert_eq!(single.len(), tiled.len());
+        for (index, (a, b)) in single.iter().zip(tiled.iter()).enumerate() {
+            let (x, y) = (index as i32 % width, index as i32 / width);
+            assert_eq!(a, b, "pixel ({}, {}) differs", x, y);
+        }
+    }
+
+    #[test]
+    fn matches_single_threaded_output() {
+        let mut single = Raqote::new(WIDTH, HEIGHT);
+        let mut tiled = Raqote::new(WIDTH, HEIGHT);
+        tiled.set_tiled_rendering(Some(TiledRendering { tile_height: 16 }));
+
+        // A full frame, then a frame only repainting the damaged region
+        for offset in [0., 37.5].iter() {
+            let collection = scene(*offset);
+            assert!(single.render_collection(&collection, WIDTH, HEIGHT));
+            assert!(tiled.render_collection(&collection, WIDTH, HEIGHT));
+            assert_same_pixels(&single, &tiled);
+        }
+    }
+
+    #[test]
+    fn matches_single_threaded_output_at_a_scale() {
+        let (width, height) = (WIDTH * 2, HEIGHT * 2);
+        let mut single = Raqote::new(width, height);
+        let mut tiled = Raqote::new(width, height);
+        tiled.set_tiled_rendering(Some(TiledRendering { tile_height: 16 }));
+
+        // Rich text spans are scaled by the painters too
+        let style = TextStyle::new(14, Color::new(255, 10, 10, 10));
+        for raqote in [&mut single, &mut tiled].iter_mut() {
+            raqote.set_scale(2.);
+            raqote.set_rich_text(2, vec![TextSpan::new("Scaled", style.clone())]);
+        }
+
+        for offset in [0., 37.5].iter() {
+            let collection = scene(*offset);
+            assert!(single.render_collection(&collection, width, height));
+            assert!(tiled.render_collection(&collection, width, height));
+            assert_same_pixels(&single, &tiled);
+        }
+    }
+}
@@ -0,0 +1,659 @@
+//! Builder of the windows of [`DisplayMinifb`]
+
+use crate::cursor::{Cursor, CursorControl};
+use crate::{scale_factor_from_env, DisplayMinifb, MouseState, WindowState, DEFAULT_UPDATE_RATE};
+
+use hyber::display::DisplayDescritor;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+/// How the alpha of the pixels is given to a transparent window
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PresentAlpha {
+    /// Colors are divided by their alpha before being presented
+    Straight,
+    /// Colors are presented already multiplied by their alpha, as drawn
+    Premultiplied,
+}
+
+/// How a fixed resolution fits in a window of another size
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FitMode {
+    /// Stretches the contents over the whole window
+    Stretch,
+    /// Scales the contents as much as fits, keeping their aspect ratio,
+    /// with bars on the sides left uncovered
+    Letterbox,
+    /// Centers the contents without scaling them
+    Center,
+}
+
+impl FitMode {
+    /// Gets the scale mode of [`minifb`](`crate`) fitting the buffer this way
+    pub fn scale_mode(self) -> minifb::ScaleMode {
+        match self {
+            FitMode::Stretch => minifb::ScaleMode::Stretch,
+            FitMode::Letterbox => minifb::ScaleMode::AspectRatioStretch,
+            FitMode::Center => minifb::ScaleMode::Center,
+        }
+    }
+
+    /// Converts `point` of a window of size `window` to the point of the
+    /// surface of size `surface` drawn there this way, all in physical
+    /// pixels, or `None` if the window or the surface has no area
+    pub fn to_surface(
+        self,
+        window: (f32, f32),
+        surface: (f32, f32),
+        point: (f32, f32),
+    ) -> Option<(f32, f32)> {
+        if !(window.0 > 0. && window.1 > 0. && surface.0 > 0. && surface.1 > 0.) {
+            return None;
+        }
+        let (scale_x, scale_y) = match self {
+            FitMode::Stretch => (window.0 / surface.0, window.1 / surface.1),
+            FitMode::Letterbox => {
+                let scale = (window.0 / surface.0).min(window.1 / surface.1);
+                (scale, scale)
+            }
+            FitMode::Center => (1., 1.),
+        };
+        // The contents are centered, with the bars around them left uncovered
+        let offset_x = (window.0 - surface.0 * scale_x) / 2.;
+        let offset_y = (window.1 - surface.1 * scale_y) / 2.;
+        Some(((point.0 - offset_x) / scale_x, (point.1 - offset_y) / scale_y))
+    }
+}
+
+/// Event of a window that has no [`hyber`](`crate`) event, queued on
+/// [`DisplayMinifb::window_events`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WindowEvent {
+    /// The window gained the keyboard focus
+    FocusGained,
+    /// The window lost the keyboard focus
+    FocusLost,
+    /// Move of the window, in logical units
+    Moved {
+        /// The current position of the window's left edge on the screen
+        x: i32,
+        /// The current position of the window's top edge on the screen
+        y: i32,
+    },
+    /// The window was minimized, as far as it can be detected, which is
+    /// told by [`WindowState::minimized`](crate::WindowState::minimized)
+    Minimized,
+    /// The window was restored after being minimized
+    Restored,
+    /// Activation of a menu item, from the menu bar or its accelerator
+    MenuActivated {
+        /// Identifier given to the item in its [`MenuTree`](crate::menu::MenuTree)
+        id: usize,
+    },
+}
+
+/// Close requests of a window and their answer, which can be shared with
+/// the messages handling them
+///
+/// Closing the window, by its close button or the quit key, is only
+/// requested at first. The request goes through unless it is vetoed before
+/// the window's events are detected again.
+#[derive(Default)]
+pub struct CloseControl {
+    requested: bool,
+    vetoed: bool,
+}
+
+impl CloseControl {
+    /// Whether closing the window was requested, and the request was not
+    /// answered yet
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+
+    pub(crate) fn set_requested(&mut self, requested: bool) {
+        self.requested = requested;
+    }
+
+    /// Keeps the window open after the pending close request, such as to
+    /// ask about unsaved changes first
+    ///
+    /// The veto is read the next time the window's events are detected,
+    /// and is forgotten if no close request is pending by then.
+    pub fn veto(&mut self) {
+        self.vetoed = true;
+    }
+
+    /// Whether the pending close request was vetoed, clearing the veto
+    pub(crate) fn take_veto(&mut self) -> bool {
+        std::mem::take(&mut self.vetoed)
+    }
+}
+
+/// Reasons why a [`DisplayBuilder`] can't create a window
+#[derive(Debug)]
+pub enum DisplayError {
+    /// The width or the height of the window is zero
+    ZeroSize,
+    /// The scale factor is not a positive number
+    InvalidScaleFactor(f64),
+    /// Both the scale factor and the scale of [`minifb`](`crate`) enlarge
+    /// the window
+    ConflictingScales,
+    /// The minimum size is larger than the maximum size
+    MinimumAboveMaximum,
+    /// The size of the window is not within its minimum and maximum sizes
+    SizeOutOfBounds,
+    /// A borderless window was given a title bar
+    TitleBarWithoutBorder,
+    /// A minimizable window was given no title bar, which holds the
+    /// minimize button
+    MinimizableWithoutTitleBar,
+    /// Minimum or maximum sizes were given to a window with a fixed
+    /// resolution, which is never laid out at another size
+    SizeLimitsWithFixedResolution,
+    /// A background color component is above 255
+    InvalidBackgroundColor,
+    /// [`minifb`](`crate`) failed to create the window
+    Window(minifb::Error),
+    /// The minimize button was asked to be shown or hidden on its own,
+    /// which [`minifb`](`crate`) can't do
+    MinimizableUnsupported,
+}
+
+impl fmt::Display for DisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisplayError::ZeroSize => write!(f, "the window's width and height must not be zero"),
+            DisplayError::InvalidScaleFactor(scale) => {
+                write!(f, "the scale factor must be a positive number, not {}", scale)
+            }
+            DisplayError::ConflictingScales => write!(
+                f,
+                "a scale factor can't be combined with a minifb scale other than X1"
+            ),
+            DisplayError::MinimumAboveMaximum => {
+                write!(f, "the minimum size is larger than the maximum size")
+            }
+            DisplayError::SizeOutOfBounds => {
+                write!(f, "the size is not within the minimum and maximum sizes")
+            }
+            DisplayError::TitleBarWithoutBorder => {
+                write!(f, "a borderless window can't have a title bar")
+            }
+            DisplayError::MinimizableWithoutTitleBar => {
+                write!(f, "a window without a title bar can't be minimized")
+            }
+            DisplayError::SizeLimitsWithFixedResolution => {
+                write!(f, "a window with a fixed resolution can't have minimum or maximum sizes")
+            }
+            DisplayError::InvalidBackgroundColor => {
+                write!(f, "the background color components must not be above 255")
+            }
+            DisplayError::Window(error) => write!(f, "failed to create the window: {}", error),
+            DisplayError::MinimizableUnsupported => write!(
+                f,
+                "the minimize button can't be changed without the title bar and close button"
+            ),
+        }
+    }
+}
+
+impl Error for DisplayError {}
+
+/// Options of a [`DisplayMinifb`], checked when the window is built
+///
+/// Sizes and positions are in logical units, which are multiplied by the
+/// scale factor to get the window's physical pixels.
+pub struct DisplayBuilder {
+    title: String,
+    width: usize,
+    height: usize,
+    borderless: bool,
+    title_bar: bool,
+    resizable: bool,
+    topmost: bool,
+    minimizable: bool,
+    transparency: bool,
+    present_alpha: PresentAlpha,
+    scale: minifb::Scale,
+    scale_mode: minifb::ScaleMode,
+    scale_factor: Option<f64>,
+    position: Option<(isize, isize)>,
+    min_size: Option<(usize, usize)>,
+    max_size: Option<(usize, usize)>,
+    background_color: Option<(usize, usize, usize)>,
+    quit_key: Option<minifb::Key>,
+    fixed_resolution: Option<(usize, usize, FitMode)>,
+}
+
+impl DisplayBuilder {
+    /// Starts a window of `width` by `height` logical units, with a border
+    /// and a title bar
+    pub fn new(title: &str, width: usize, height: usize) -> Self {
+        DisplayBuilder {
+            title: title.to_string(),
+            width,
+            height,
+            borderless: false,
+            title_bar: true,
+            resizable: false,
+            topmost: false,
+            minimizable: true,
+            transparency: false,
+            present_alpha: PresentAlpha::Straight,
+            scale: minifb::Scale::X1,
+            scale_mode: minifb::ScaleMode::UpperLeft,
+            scale_factor: None,
+            position: None,
+            min_size: None,
+            max_size: None,
+            background_color: None,
+            quit_key: Some(minifb::Key::Escape),
+            fixed_resolution: None,
+        }
+    }
+
+    /// Starts a window with the options of a [`DisplayDescritor`]
+    ///
+    /// Every descriptor is accepted, where a borderless window has no title
+    /// bar even if the descriptor asks for one.
+    pub fn from_descriptor(
+        title: &str,
+        width: usize,
+        height: usize,
+        display_descriptor: DisplayDescritor,
+    ) -> Self {
+        let titled = display_descriptor.titled && display_descriptor.border;
+        DisplayBuilder::new(title, width, height)
+            .borderless(!display_descriptor.border)
+            .title_bar(titled)
+            .resizable(display_descriptor.resizable)
+            .topmost(display_descriptor.topmost)
+            .minimizable(titled)
+    }
+
+    /// Whether the window has no border
+    pub fn borderless(mut self, borderless: bool) -> Self {
+        self.borderless = borderless;
+        self
+    }
+
+    /// Whether the window has a title bar
+    pub fn title_bar(mut self, title_bar: bool) -> Self {
+        self.title_bar = title_bar;
+        self
+    }
+
+    /// Whether the window can be resized by the user
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Whether the window stays on top of the other windows
+    pub fn topmost(mut self, topmost: bool) -> Self {
+        self.topmost = topmost;
+        self
+    }
+
+    /// Whether the window can be minimized by the user, which needs a
+    /// title bar
+    ///
+    /// Windows with a title bar can always be minimized, since
+    /// [`minifb`](`crate`) has no option for the minimize button alone.
+    pub fn minimizable(mut self, minimizable: bool) -> Self {
+        self.minimizable = minimizable;
+        self
+    }
+
+    /// Whether the window is see-through where the drawn pixels are
+    /// translucent, on the platforms supporting it
+    pub fn transparency(mut self, transparency: bool) -> Self {
+        self.transparency = transparency;
+        self
+    }
+
+    /// How the alpha of the pixels is given to a transparent window,
+    /// which depends on the platform's compositor
+    pub fn present_alpha(mut self, present_alpha: PresentAlpha) -> Self {
+        self.present_alpha = present_alpha;
+        self
+    }
+
+    /// Scale [`minifb`](`crate`) applies to the buffer, enlarging its pixels
+    pub fn scale(mut self, scale: minifb::Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// How [`minifb`](`crate`) fits a buffer of another size in the window
+    pub fn scale_mode(mut self, scale_mode: minifb::ScaleMode) -> Self {
+        self.scale_mode = scale_mode;
+        self
+    }
+
+    /// Physical pixels per logical unit, instead of the one found in
+    /// [`SCALE_FACTOR_VAR`](crate::SCALE_FACTOR_VAR) or 1
+    pub fn scale_factor(mut self, scale_factor: f64) -> Self {
+        self.scale_factor = Some(scale_factor);
+        self
+    }
+
+    /// Position of the window's top left corner on the screen
+    pub fn position(mut self, x: isize, y: isize) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Smallest size the window's contents are laid out at
+    pub fn min_size(mut self, width: usize, height: usize) -> Self {
+        self.min_size = Some((width, height));
+        self
+    }
+
+    /// Largest size the window's contents are laid out at
+    pub fn max_size(mut self, width: usize, height: usize) -> Self {
+        self.max_size = Some((width, height));
+        self
+    }
+
+    /// Color shown where the buffer doesn't cover the window
+    pub fn background_color(mut self, red: usize, green: usize, blue: usize) -> Self {
+        self.background_color = Some((red, green, blue));
+        self
+    }
+
+    /// Key requesting to close the window, Escape by default, or `None`
+    /// to only close it from its close button
+    ///
+    /// Windows where Escape dismisses something else, such as a dialog,
+    /// should opt out or pick another key, since every press would also
+    /// request to close the window.
+    pub fn quit_key(mut self, quit_key: Option<minifb::Key>) -> Self {
+        self.quit_key = quit_key;
+        self
+    }
+
+    /// Lays the contents out at `width` by `height` logical units whatever
+    /// the window's size, fitting them in the window with `fit`
+    ///
+    /// This replaces the [`DisplayBuilder::scale_mode`].
+    pub fn fixed_resolution(mut self, width: usize, height: usize, fit: FitMode) -> Self {
+        self.fixed_resolution = Some((width, height, fit));
+        self
+    }
+
+    /// Checks that the options can be combined
+    fn validate(&self, scale_factor: f64) -> Result<(), DisplayError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(DisplayError::ZeroSize);
+        }
+        if !(scale_factor > 0. && scale_factor.is_finite()) {
+            return Err(DisplayError::InvalidScaleFactor(scale_factor));
+        }
+        if scale_factor != 1. && !matches!(self.scale, minifb::Scale::X1) {
+            return Err(DisplayError::ConflictingScales);
+        }
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min.0 > max.0 || min.1 > max.1 {
+                return Err(DisplayError::MinimumAboveMaximum);
+            }
+        }
+        let below_min = self
+            .min_size
+            .map_or(false, |min| self.width < min.0 || self.height < min.1);
+        let above_max = self
+            .max_size
+            .map_or(false, |max| self.width > max.0 || self.height > max.1);
+        if below_min || above_max {
+            return Err(DisplayError::SizeOutOfBounds);
+        }
+        if let Some((width, height, _)) = self.fixed_resolution {
+            if width == 0 || height == 0 {
+                return Err(DisplayError::ZeroSize);
+            }
+            if self.min_size.is_some() || self.max_size.is_some() {
+                return Err(DisplayError::SizeLimitsWithFixedResolution);
+            }
+        }
+        if self.borderless && self.title_bar {
+            return Err(DisplayError::TitleBarWithoutBorder);
+        }
+        if self.minimizable && !self.title_bar {
+            return Err(DisplayError::MinimizableWithoutTitleBar);
+        }
+        if let Some((red, green, blue)) = self.background_color {
+            if red > 255 || green > 255 || blue > 255 {
+                return Err(DisplayError::InvalidBackgroundColor);
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the window
+    pub fn build(self) -> Result<DisplayMinifb, DisplayError> {
+        let scale_factor = self
+            .scale_factor
+            .or_else(scale_factor_from_env)
+            .unwrap_or(1.);
+        self.validate(scale_factor)?;
+
+        let physical_width = (self.width as f64 * scale_factor).round() as usize;
+        let physical_height = (self.height as f64 * scale_factor).round() as usize;
+        let options = minifb::WindowOptions {
+            borderless: self.borderless,
+            title: self.title_bar,
+            resize: self.resizable,
+            topmost: self.topmost,
+            scale: self.scale,
+            scale_mode: self
+                .fixed_resolution
+                .map_or(self.scale_mode, |(_, _, fit)| fit.scale_mode()),
+            transparency: self.transparency,
+        };
+        let mut display =
+            minifb::Window::new(&self.title, physical_width, physical_height, options)
+                .map_err(DisplayError::Window)?;
+
+        if let Some((x, y)) = self.position {
+            let x = (x as f64 * scale_factor).round() as isize;
+            let y = (y as f64 * scale_factor).round() as isize;
+            display.set_position(x, y);
+        }
+        if let Some((red, green, blue)) = self.background_color {
+            display.set_background_color(red, green, blue);
+        }
+        let position = display.get_position();
+
+        Ok(DisplayMinifb {
+            display,
+            mouse_state: MouseState::default(),
+            window_state: WindowState {
+                window_size: (physical_width, physical_height),
+                border: !self.borderless,
+                resizable: self.resizable,
+                // Windows with a title bar can always be minimized
+                minimizable: self.title_bar,
+                topmost: self.topmost,
+                close_requested: false,
+                focused: false,
+                position,
+                minimized: false,
+            },
+            input_detected: false,
+            scale_factor,
+            needs_redraw: false,
+            present_alpha: self.present_alpha,
+            cursor: Rc::new(RefCell::new(CursorControl::default())),
+            close_control: Rc::new(RefCell::new(CloseControl::default())),
+            window_events: Rc::new(RefCell::new(VecDeque::new())),
+            applied_cursor: Cursor::Arrow,
+            quit_key: self.quit_key,
+            update_rate: Some(DEFAULT_UPDATE_RATE),
+            paced: false,
+            closed: false,
+            menu_bar: Vec::new(),
+            menu_handles: Vec::new(),
+            title: self.title,
+            options,
+            background_color: self.background_color,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            fixed_resolution: self.fixed_resolution,
+            last_error: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> DisplayBuilder {
+        DisplayBuilder::new("test", 640, 360)
+    }
+
+    fn error(builder: DisplayBuilder) -> Option<DisplayError> {
+        builder.validate(1.).err()
+    }
+
+    #[test]
+    fn accepts_every_descriptor() {
+        for bits in 0..16 {
+            let descriptor = DisplayDescritor {
+                border: bits & 1 != 0,
+                titled: bits & 2 != 0,
+                resizable: bits & 4 != 0,
+                topmost: bits & 8 != 0,
+            };
+            let builder = DisplayBuilder::from_descriptor("test", 640, 360, descriptor);
+            assert!(builder.validate(1.).is_ok(), "descriptor {:04b} is rejected", bits);
+        }
+    }
+
+    #[test]
+    fn accepts_the_default_options() {
+        assert!(error(builder()).is_none());
+        assert!(error(builder().min_size(320, 180).max_size(1280, 720)).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!(matches!(
+            error(DisplayBuilder::new("test", 0, 360)),
+            Some(DisplayError::ZeroSize)
+        ));
+        assert!(matches!(
+            error(builder().min_size(800, 100).max_size(700, 720)),
+            Some(DisplayError::MinimumAboveMaximum)
+        ));
+        assert!(matches!(
+            error(builder().max_size(600, 720)),
+            Some(DisplayError::SizeOutOfBounds)
+        ));
+        assert!(matches!(
+            error(builder().min_size(100, 400)),
+            Some(DisplayError::SizeOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_scales() {
+        for scale in [0., -1., f64::NAN, f64::INFINITY].iter() {
+            assert!(matches!(
+                builder().validate(*scale),
+                Err(DisplayError::InvalidScaleFactor(_))
+            ));
+        }
+        assert!(matches!(
+            builder().scale(minifb::Scale::X2).validate(2.),
+            Err(DisplayError::ConflictingScales)
+        ));
+        assert!(builder().scale(minifb::Scale::X2).validate(1.).is_ok());
+    }
+
+    #[test]
+    fn rejects_conflicting_decorations() {
+        assert!(matches!(
+            error(builder().borderless(true)),
+            Some(DisplayError::TitleBarWithoutBorder)
+        ));
+        assert!(matches!(
+            error(builder().title_bar(false)),
+            Some(DisplayError::MinimizableWithoutTitleBar)
+        ));
+        assert!(error(builder().borderless(true).title_bar(false).minimizable(false)).is_none());
+    }
+
+    #[test]
+    fn rejects_other_invalid_options() {
+        assert!(matches!(
+            error(builder().background_color(256, 0, 0)),
+            Some(DisplayError::InvalidBackgroundColor)
+        ));
+        assert!(matches!(
+            error(builder().fixed_resolution(0, 360, FitMode::Letterbox)),
+            Some(DisplayError::ZeroSize)
+        ));
+        assert!(matches!(
+            error(builder().fixed_resolution(640, 360, FitMode::Stretch).min_size(320, 180)),
+            Some(DisplayError::SizeLimitsWithFixedResolution)
+        ));
+    }
+
+    /// Checks that `fit` maps `point` of `window` close to `expected` of a
+    /// surface of 640 by 360 pixels
+    fn check_fit(fit: FitMode, window: (f32, f32), point: (f32, f32), expected: (f32, f32)) {
+        let (x, y) = fit.to_surface(window, (640., 360.), point).unwrap();
+        assert!(
+            (x - expected.0).abs() < 1e-3 && (y - expected.1).abs() < 1e-3,
+            "{:?} of {:?} maps {:?} to {:?}, not {:?}",
+            fit,
+            window,
+            point,
+            (x, y),
+            expected
+        );
+    }
+
+    #[test]
+    fn stretches_the_surface_over_the_window() {
+        check_fit(FitMode::Stretch, (301., 157.), (0., 0.), (0., 0.));
+        check_fit(FitMode::Stretch, (301., 157.), (301., 157.), (640., 360.));
+        check_fit(FitMode::Stretch, (1281., 361.), (640.5, 180.5), (320., 180.));
+    }
+
+    #[test]
+    fn letterboxes_the_surface_in_the_window() {
+        // A wide window has bars on the sides, a tall window above and below
+        check_fit(FitMode::Letterbox, (1000., 270.), (260., 0.), (0., 0.));
+        check_fit(FitMode::Letterbox, (1000., 270.), (740., 270.), (640., 360.));
+        check_fit(FitMode::Letterbox, (1000., 270.), (100., 135.), (-213.333, 180.));
+        check_fit(FitMode::Letterbox, (320., 999.), (0., 409.5), (0., 0.));
+        check_fit(FitMode::Letterbox, (320., 999.), (160., 499.5), (320., 180.));
+        check_fit(FitMode::Letterbox, (301., 157.), (150.5, 78.5), (320., 180.));
+    }
+
+    #[test]
+    fn centers_the_surface_in_the_window() {
+        check_fit(FitMode::Center, (800., 401.), (80., 20.5), (0., 0.));
+        // Windows smaller than the surface show its middle
+        check_fit(FitMode::Center, (300., 200.), (0., 0.), (170., 80.));
+        check_fit(FitMode::Center, (300., 200.), (150., 100.), (320., 180.));
+    }
+
+    #[test]
+    fn maps_nothing_without_area() {
+        for fit in [FitMode::Stretch, FitMode::Letterbox, FitMode::Center].iter() {
+            for window in [(0., 0.), (0., 360.), (640., 0.)].iter() {
+                assert_eq!(fit.to_surface(*window, (640., 360.), (0., 0.)), None);
+            }
+            assert_eq!(fit.to_surface((640., 360.), (0., 360.), (0., 0.)), None);
+        }
+    }
+}
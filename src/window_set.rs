@@ -0,0 +1,269 @@
+//! Several windows driven by one application
+//!
+//! [`Renderer`] draws a single collection on a single display, so each
+//! window of a [`WindowSet`] has a [`Raqote`] of its own, along with its
+//! instruction collection and its events. The application loop calls
+//! [`WindowSet::detect_events`], handles the events of each window and
+//! then calls [`WindowSet::draw`], until [`WindowSet::is_running`] is false.
+
+use crate::{DisplayMinifb, EventClient, Raqote};
+
+use hyber::display::Display;
+use hyber::event;
+use hyber::renderer::{RenderInstructionCollection, Renderer};
+use hyber::util::Queue;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Identifier of a window of a [`WindowSet`]
+pub type WindowId = usize;
+
+/// A window with everything needed to draw it and handle its events
+pub struct AppWindow {
+    /// The window
+    pub display: DisplayMinifb,
+    /// Renderer holding the window's draw target
+    pub renderer: Raqote,
+    /// Instructions drawn on the window
+    pub collection: Rc<RefCell<RenderInstructionCollection>>,
+    /// Events detected on the window and not handled yet
+    pub events: Queue<event::Event>,
+    /// Whether closing the window ends the application
+    pub primary: bool,
+}
+
+/// What the bookkeeping of a [`WindowSet`] needs to know of a window
+trait Registered {
+    /// Whether closing the window ends the application
+    fn is_primary(&self) -> bool;
+    /// Whether the window is still open
+    fn is_open(&self) -> bool;
+}
+
+impl Registered for AppWindow {
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
+
+    fn is_open(&self) -> bool {
+        self.display.is_open()
+    }
+}
+
+/// Windows by identifier, with identifiers never given twice
+struct Registry<W> {
+    windows: BTreeMap<WindowId, W>,
+    next_id: WindowId,
+}
+
+impl<W> Default for Registry<W> {
+    fn default() -> Self {
+        Registry {
+            windows: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<W: Registered> Registry<W> {
+    fn add(&mut self, window: W) -> WindowId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.windows.insert(id, window);
+        id
+    }
+
+    fn is_running(&self) -> bool {
+        self.windows.values().any(W::is_primary)
+    }
+
+    /// Drops the windows that are no longer open, returning their identifiers
+    fn remove_closed(&mut self) -> Vec<WindowId> {
+        let closed: Vec<WindowId> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| !window.is_open())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in closed.iter() {
+            self.windows.remove(id);
+        }
+        closed
+    }
+
+    /// Windows in the order they are drawn, the secondary ones first
+    fn draw_order(&mut self) -> Vec<&mut W> {
+        let mut windows: Vec<&mut W> = self.windows.values_mut().collect();
+        windows.sort_by_key(|window| window.is_primary());
+        windows
+    }
+}
+
+/// Windows of an application, where closing the primary window ends it
+/// and closing any other window only drops that window
+#[derive(Default)]
+pub struct WindowSet {
+    windows: Registry<AppWindow>,
+}
+
+impl WindowSet {
+    pub fn new() -> Self {
+        WindowSet::default()
+    }
+
+    /// Adds a window, returning its identifier
+    ///
+    /// Only the first primary window should keep the frame pacing of its
+    /// renderer, since every window is drawn on each frame.
+    pub fn add(&mut self, mut display: DisplayMinifb, primary: bool) -> WindowId {
+        let (width, height) = display.surface_size();
+        let renderer = Raqote::new(width as i32, height as i32);
+        if !primary {
+            // The primary window already waits for the next frame
+            display.set_update_rate(None);
+        }
+
+        self.windows.add(AppWindow {
+            display,
+            renderer,
+            collection: Rc::new(RefCell::new(RenderInstructionCollection::new())),
+            events: Queue::new(),
+            primary,
+        })
+    }
+
+    pub fn get(&self, id: WindowId) -> Option<&AppWindow> {
+        self.windows.windows.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: WindowId) -> Option<&mut AppWindow> {
+        self.windows.windows.get_mut(&id)
+    }
+
+    /// Closes a window, returning it
+    pub fn close(&mut self, id: WindowId) -> Option<AppWindow> {
+        self.windows.windows.remove(&id)
+    }
+
+    /// Identifiers of the open windows
+    pub fn ids(&self) -> Vec<WindowId> {
+        self.windows.windows.keys().copied().collect()
+    }
+
+    /// Whether a primary window is still open
+    pub fn is_running(&self) -> bool {
+        self.windows.is_running()
+    }
+
+    /// Identifier of the window with the keyboard focus, if any
+    pub fn active(&mut self) -> Option<WindowId> {
+        for (id, window) in self.windows.windows.iter_mut() {
+            if window.display.is_active() {
+                return Some(*id);
+            }
+        }
+        None
+    }
+
+    /// Detects the events of every window into its own queue, dropping the
+    /// windows closed by the user, whose identifiers are returned
+    pub fn detect_events(&mut self) -> Vec<WindowId> {
+        for window in self.windows.windows.values_mut() {
+            <Raqote as Renderer<DisplayMinifb, EventClient>>::detect_display_events(
+                &mut window.events,
+                &mut window.display,
+            );
+        }
+        self.windows.remove_closed()
+    }
+
+    /// Draws the collection of every window, the secondary ones first so
+    /// that the primary one paces the frames
+    pub fn draw(&mut self) {
+        for window in self.windows.draw_order() {
+            let collection = window.collection.borrow();
+            window.renderer.draw_collection(&collection, &mut window.display);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Window known only by its identity and flags
+    struct FakeWindow {
+        name: &'static str,
+        primary: bool,
+        open: bool,
+    }
+
+    impl Registered for FakeWindow {
+        fn is_primary(&self) -> bool {
+            self.primary
+        }
+
+        fn is_open(&self) -> bool {
+            self.open
+        }
+    }
+
+    fn window(name: &'static str, primary: bool) -> FakeWindow {
+        FakeWindow {
+            name,
+            primary,
+            open: true,
+        }
+    }
+
+    #[test]
+    fn never_reuses_identifiers() {
+        let mut registry = Registry::default();
+        let first = registry.add(window("main", true));
+        let second = registry.add(window("tools", false));
+        assert_ne!(first, second);
+        registry.windows.remove(&second);
+        let third = registry.add(window("about", false));
+        assert!(third != first && third != second);
+    }
+
+    #[test]
+    fn drops_closed_windows() {
+        let mut registry = Registry::default();
+        let main = registry.add(window("main", true));
+        let tools = registry.add(window("tools", false));
+        let about = registry.add(window("about", false));
+        assert!(registry.remove_closed().is_empty());
+
+        registry.windows.get_mut(&tools).unwrap().open = false;
+        registry.windows.get_mut(&about).unwrap().open = false;
+        assert_eq!(registry.remove_closed(), vec![tools, about]);
+        assert_eq!(registry.windows.keys().copied().collect::<Vec<_>>(), vec![main]);
+    }
+
+    #[test]
+    fn runs_while_a_primary_window_is_open() {
+        let mut registry = Registry::default();
+        assert!(!registry.is_running());
+        let main = registry.add(window("main", true));
+        registry.add(window("tools", false));
+        assert!(registry.is_running());
+
+        // Secondary windows alone don't keep the application running
+        registry.windows.get_mut(&main).unwrap().open = false;
+        registry.remove_closed();
+        assert!(!registry.is_running());
+    }
+
+    #[test]
+    fn draws_primary_windows_last() {
+        let mut registry = Registry::default();
+        registry.add(window("main", true));
+        registry.add(window("tools", false));
+        registry.add(window("about", false));
+        let order: Vec<&str> = registry.draw_order().iter().map(|window| window.name).collect();
+        assert_eq!(order, vec!["tools", "about", "main"]);
+    }
+}